@@ -0,0 +1,96 @@
+use crate::convert;
+use crate::error::ScrapeError;
+use crate::helpers;
+use crate::Post;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+/// Walks through the posts without an id, oldest first, pairing each with
+/// the lowest id still missing from the archive. The user accepts the
+/// suggestion, types another id, skips the post or quits; accepted ids are
+/// stored on the posts and flagged `id_assigned_manually`. Returns how many
+/// ids were assigned.
+pub fn assign_ids(
+    backup: &mut [Post],
+    mut input: impl BufRead,
+    mut out: impl Write,
+) -> Result<usize, ScrapeError> {
+    let mut suggestions = helpers::missing_ids(backup);
+    let mut taken: HashMap<usize, String> = backup
+        .iter()
+        .filter_map(|post| Some((post.id.as_ref()?.parse().ok()?, post.URL.clone())))
+        .collect();
+
+    let mut candidates: Vec<usize> = (0..backup.len())
+        .filter(|&index| backup[index].id.is_none())
+        .collect();
+    candidates.sort_by_cached_key(|&index| {
+        (
+            helpers::post_date(&backup[index]),
+            backup[index].URL.clone(),
+        )
+    });
+    if candidates.is_empty() {
+        writeln!(out, "Every post already has an id")?;
+        return Ok(0);
+    }
+
+    let mut assigned = 0;
+    'posts: for (position, &index) in candidates.iter().enumerate() {
+        let post = &mut backup[index];
+        writeln!(
+            out,
+            "\n[{}/{}] {} ({})\n      {}",
+            position + 1,
+            candidates.len(),
+            convert::html_to_text(&post.title),
+            post.date
+                .map(|date| date.to_string())
+                .unwrap_or_else(|| "undated".to_string()),
+            post.URL
+        )?;
+
+        loop {
+            let suggestion = suggestions.first().copied();
+            match suggestion {
+                Some(id) => write!(out, "Id [{}], another number, s to skip or q to quit: ", id)?,
+                None => write!(out, "Id, s to skip or q to quit: ")?,
+            }
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                break 'posts;
+            }
+            let id = match line.trim() {
+                "q" => break 'posts,
+                "s" => continue 'posts,
+                "" => match suggestion {
+                    Some(id) => id,
+                    None => continue,
+                },
+                other => match other.parse::<usize>() {
+                    Ok(id) => id,
+                    Err(_) => {
+                        writeln!(out, "{:?} is not a number", other)?;
+                        continue;
+                    }
+                },
+            };
+            if let Some(url) = taken.get(&id) {
+                writeln!(out, "Id {} is already used by {}", id, url)?;
+                continue;
+            }
+
+            post.id = Some(id.to_string());
+            post.id_assigned_manually = true;
+            taken.insert(id, post.URL.clone());
+            suggestions.retain(|&missing| missing != id);
+            assigned += 1;
+            continue 'posts;
+        }
+    }
+
+    Ok(assigned)
+}
@@ -0,0 +1,180 @@
+use crate::fetcher::Fetcher;
+use crate::helpers;
+use crate::Attachment;
+use reqwest::Url;
+use scraper::{ElementRef, Selector};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Extensions of linked files worth keeping; anything else is treated as a page.
+const FILE_EXTENSIONS: [&str; 15] = [
+    "pdf", "doc", "docx", "odt", "rtf", "txt", "epub", "xls", "xlsx", "ods", "ppt", "pptx", "odp",
+    "zip", "mp3",
+];
+
+/// Finds links in a post body to documents rather than pages: files with a
+/// known extension, Google Drive and Docs, and Scribd.
+pub fn extract_attachments(body: ElementRef, post_url: &str) -> Vec<Attachment> {
+    let Ok(selector) = Selector::parse("a[href]") else {
+        return Vec::new();
+    };
+    let base = Url::parse(post_url).ok();
+    let mut attachments: Vec<Attachment> = Vec::new();
+    for link in body.select(&selector) {
+        let Some(href) = link.value().attr("href") else {
+            continue;
+        };
+        let Some(url) = base
+            .as_ref()
+            .and_then(|base| base.join(href).ok())
+            .or_else(|| Url::parse(href).ok())
+        else {
+            continue;
+        };
+        let Some(kind) = attachment_kind(&url) else {
+            continue;
+        };
+        if attachments.iter().any(|seen| seen.url == url.as_str()) {
+            continue;
+        }
+        let text = link.text().collect::<Vec<_>>().join(" ");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        attachments.push(Attachment {
+            url: url.to_string(),
+            kind,
+            text: (!text.is_empty()).then_some(text),
+            file: None,
+        });
+    }
+    attachments
+}
+
+fn attachment_kind(url: &Url) -> Option<String> {
+    let host = url.host_str()?.trim_start_matches("www.");
+    let path = url.path();
+    match host {
+        "drive.google.com" if path.starts_with("/file/") || path == "/open" || path == "/uc" => {
+            Some("google-drive".to_string())
+        }
+        "docs.google.com"
+            if ["/document/", "/spreadsheets/", "/presentation/"]
+                .iter()
+                .any(|prefix| path.starts_with(prefix)) =>
+        {
+            Some("google-docs".to_string())
+        }
+        "scribd.com" if path.starts_with("/doc/") || path.starts_with("/document/") => {
+            Some("scribd".to_string())
+        }
+        _ => {
+            let extension = path.rsplit_once('.')?.1.to_lowercase();
+            FILE_EXTENSIONS
+                .contains(&extension.as_str())
+                .then_some(extension)
+        }
+    }
+}
+
+/// Address serving the file itself, when there is one. Scribd only offers
+/// downloads to signed-in users, so its documents are recorded but not saved.
+fn download_url(attachment: &Attachment) -> Option<String> {
+    let url = Url::parse(&attachment.url).ok()?;
+    match attachment.kind.as_str() {
+        "google-drive" => {
+            let id = url
+                .query_pairs()
+                .find(|(key, _)| key == "id")
+                .map(|(_, id)| id.into_owned())
+                .or_else(|| {
+                    let mut segments = url.path_segments()?;
+                    segments.find(|segment| *segment == "d")?;
+                    segments.next().map(str::to_string)
+                })?;
+            Some(format!(
+                "https://drive.google.com/uc?export=download&id={}",
+                id
+            ))
+        }
+        "google-docs" => {
+            let mut segments = url.path_segments()?;
+            let kind = segments.next()?;
+            segments.find(|segment| *segment == "d")?;
+            let id = segments.next()?;
+            let format = if kind == "spreadsheets" {
+                "xlsx"
+            } else {
+                "pdf"
+            };
+            Some(format!(
+                "https://docs.google.com/{}/d/{}/export?format={}",
+                kind, id, format
+            ))
+        }
+        "scribd" => None,
+        _ => Some(attachment.url.clone()),
+    }
+}
+
+/// Saves every downloadable attachment into `dir` as `<hash>.<ext>`,
+/// recording the file name on the attachment. Files already present from an
+/// earlier run are reused; failures are logged and leave `file` empty.
+pub fn download_attachments(fetcher: &Fetcher, attachments: &mut [Attachment], dir: &Path) {
+    for attachment in attachments.iter_mut() {
+        let Some(url) = download_url(attachment) else {
+            continue;
+        };
+        let stem = &helpers::sha256_hex(attachment.url.as_bytes())[..16];
+        if let Some(existing) = existing_file(dir, stem) {
+            attachment.file = Some(existing);
+            continue;
+        }
+        match fetcher.fetch_bytes(&url) {
+            // Drive answers files too large to virus-scan with a warning page
+            Ok((_, Some(content_type))) if content_type.starts_with("text/html") => {
+                warn!(url = %attachment.url, "attachment is not directly downloadable");
+            }
+            Ok((bytes, content_type)) => {
+                let extension = file_extension(attachment, content_type.as_deref());
+                let name = format!("{}.{}", stem, extension);
+                match fs::create_dir_all(dir).and_then(|()| fs::write(dir.join(&name), &bytes)) {
+                    Ok(()) => {
+                        info!(url = %attachment.url, file = %name, "saved attachment");
+                        attachment.file = Some(name);
+                    }
+                    Err(e) => warn!(url = %attachment.url, error = %e, "failed to save attachment"),
+                }
+            }
+            Err(e) => warn!(url = %attachment.url, error = %e, "failed to download attachment"),
+        }
+    }
+}
+
+fn existing_file(dir: &Path, stem: &str) -> Option<String> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .find(|name| {
+            name.strip_prefix(stem)
+                .is_some_and(|rest| rest.starts_with('.'))
+        })
+}
+
+fn file_extension(attachment: &Attachment, content_type: Option<&str>) -> String {
+    if FILE_EXTENSIONS.contains(&attachment.kind.as_str()) {
+        return attachment.kind.clone();
+    }
+    let extension = match content_type.unwrap_or("").split(';').next().unwrap_or("") {
+        "application/pdf" => "pdf",
+        "application/msword" => "doc",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation" => "pptx",
+        "application/zip" => "zip",
+        "application/epub+zip" => "epub",
+        "text/plain" => "txt",
+        _ => "bin",
+    };
+    extension.to_string()
+}
@@ -0,0 +1,96 @@
+use crate::error::ScrapeError;
+use crate::fetcher::Validators;
+use crate::helpers;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Stores raw page HTML on disk so later runs can re-parse pages without
+/// downloading them again. Each URL is kept as `<sha256>.html` next to a
+/// `<sha256>.json` file describing where and when it was fetched. Used both
+/// for the HTTP cache and for post snapshots.
+pub struct PageCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CacheEntry {
+    url: String,
+    fetched_at: String,
+    #[serde(flatten)]
+    validators: Validators,
+}
+
+impl PageCache {
+    pub fn new(dir: PathBuf) -> Result<Self, ScrapeError> {
+        fs::create_dir_all(&dir)?;
+        Ok(PageCache { dir })
+    }
+
+    /// Reads a stored page. When `expected` is given the page is only
+    /// returned if it was stored with those validators, so a copy older than
+    /// the latest response seen for the URL is treated as missing.
+    pub fn get(
+        &self,
+        url: &str,
+        expected: Option<&Validators>,
+    ) -> Result<Option<String>, ScrapeError> {
+        if let Some(expected) = expected {
+            let entry: CacheEntry = match fs::read_to_string(self.path(url, "json")) {
+                Ok(json) => serde_json::from_str(&json)?,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            if entry.validators != *expected {
+                return Ok(None);
+            }
+        }
+        match fs::read_to_string(self.path(url, "html")) {
+            Ok(html) => Ok(Some(html)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn put(&self, url: &str, html: &str, validators: &Validators) -> Result<(), ScrapeError> {
+        let entry = CacheEntry {
+            url: url.to_string(),
+            fetched_at: Utc::now().to_rfc3339(),
+            validators: validators.clone(),
+        };
+        fs::write(self.path(url, "html"), html)?;
+        fs::write(
+            self.path(url, "json"),
+            serde_json::to_string_pretty(&entry)?,
+        )?;
+        Ok(())
+    }
+
+    /// Reads every stored page as `(url, fetched_at, html)`.
+    pub fn entries(&self) -> Result<Vec<(String, String, String)>, ScrapeError> {
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let entry: CacheEntry = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            match fs::read_to_string(path.with_extension("html")) {
+                Ok(html) => entries.push((entry.url, entry.fetched_at, html)),
+                Err(e) => warn!(url = %entry.url, error = %e, "snapshot HTML is missing"),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn path(&self, url: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{}.{}",
+            helpers::sha256_hex(url.as_bytes()),
+            extension
+        ))
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::{Condvar, Mutex};
+use tracing::{info, warn};
+
+/// Number of attempts judged together before the limit is adjusted.
+const WINDOW: usize = 20;
+
+/// Share of failed attempts in a window at which the limit is halved.
+const FAILURE_RATE_LIMIT: f64 = 0.2;
+
+/// Caps how many HTTP requests are in flight at the same time. With
+/// auto-tuning the cap starts at its maximum, is halved whenever too many
+/// attempts in a window fail or are rate limited, and grows back by one after
+/// every window without failures, so a struggling server gets relief without
+/// the user having to restart with fewer requests. Parsing is not limited.
+pub struct AdaptiveLimit {
+    state: Mutex<LimitState>,
+    released: Condvar,
+    max: usize,
+    adaptive: bool,
+}
+
+struct LimitState {
+    limit: usize,
+    active: usize,
+    attempts: usize,
+    failures: usize,
+    lowest: usize,
+}
+
+/// Held while a request is in flight; dropping it frees the slot.
+pub struct Permit<'a> {
+    owner: &'a AdaptiveLimit,
+}
+
+impl AdaptiveLimit {
+    pub fn new(max: usize, adaptive: bool) -> Self {
+        let max = max.max(1);
+        AdaptiveLimit {
+            state: Mutex::new(LimitState {
+                limit: max,
+                active: 0,
+                attempts: 0,
+                failures: 0,
+                lowest: max,
+            }),
+            released: Condvar::new(),
+            max,
+            adaptive,
+        }
+    }
+
+    /// Waits until fewer than the current limit of requests are in flight.
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut state = self.state.lock().unwrap();
+        while state.active >= state.limit {
+            state = self.released.wait(state).unwrap();
+        }
+        state.active += 1;
+        Permit { owner: self }
+    }
+
+    /// Records the outcome of one request attempt and adjusts the limit at
+    /// the end of each window.
+    pub fn record(&self, failed: bool) {
+        if !self.adaptive {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.attempts += 1;
+        if failed {
+            state.failures += 1;
+        }
+        if state.attempts < WINDOW {
+            return;
+        }
+
+        let failure_rate = state.failures as f64 / state.attempts as f64;
+        if failure_rate >= FAILURE_RATE_LIMIT && state.limit > 1 {
+            let previous = state.limit;
+            state.limit = (state.limit / 2).max(1);
+            state.lowest = state.lowest.min(state.limit);
+            warn!(
+                from = previous,
+                to = state.limit,
+                failure_rate,
+                "too many failed requests, lowering concurrency"
+            );
+        } else if state.failures == 0 && state.limit < self.max {
+            state.limit += 1;
+            info!(
+                to = state.limit,
+                "requests succeeding again, raising concurrency"
+            );
+            self.released.notify_one();
+        }
+        state.attempts = 0;
+        state.failures = 0;
+    }
+
+    /// Smallest limit reached during the run.
+    pub fn lowest(&self) -> usize {
+        self.state.lock().unwrap().lowest
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.owner.state.lock().unwrap().active -= 1;
+        self.owner.released.notify_one();
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::ScrapeError;
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A `blogs.toml` file listing every blog scraped by `scrape --all`:
+///
+/// ```toml
+/// [[blog]]
+/// name = "gnostic"
+/// url = "https://gnosticesotericstudyworkaids.blogspot.com/"
+/// output = "gnostic.json"
+/// profile = "profiles/gnostic.toml"
+/// schedule = "6h"
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BlogsConfig {
+    #[serde(rename = "blog", default)]
+    pub blogs: Vec<BlogConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BlogConfig {
+    pub name: String,
+    pub url: String,
+    /// Archive file for this blog, used in place of backup.json
+    pub output: PathBuf,
+    /// Selector profile for blogs that use a different template
+    pub profile: Option<PathBuf>,
+    /// Defaults to the --report path with the name added, e.g. run_report-<name>.json
+    pub report: Option<PathBuf>,
+    /// Minimum time between scrapes, such as "6h" or "1d"; the blog is
+    /// skipped until this long has passed since its last report
+    #[serde(default, deserialize_with = "deserialize_interval")]
+    pub schedule: Option<Duration>,
+}
+
+impl BlogsConfig {
+    pub fn load(path: &Path) -> Result<Self, ScrapeError> {
+        let raw = fs::read_to_string(path)?;
+        let config: BlogsConfig = toml::from_str(&raw)
+            .map_err(|e| ScrapeError::Config(format!("{}: {}", path.display(), e)))?;
+        if config.blogs.is_empty() {
+            return Err(ScrapeError::Config(format!(
+                "{} does not list any [[blog]] entries",
+                path.display()
+            )));
+        }
+        Ok(config)
+    }
+}
+
+/// Parses intervals such as "90s", "30m", "6h" or "2d". Zero is rejected,
+/// since a watch loop or schedule without a pause would hammer the blog.
+pub fn parse_interval(raw: &str) -> Result<Duration, String> {
+    let raw = raw.trim();
+    let split = raw
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("{:?} needs a unit (s, m, h or d)", raw))?;
+    let (amount, unit) = raw.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("{:?} does not start with a number", raw))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit {:?} in {:?}", unit, raw)),
+    };
+    if amount == 0 {
+        return Err(format!("{:?} must be longer than zero", raw));
+    }
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("{:?} is too long", raw))
+}
+
+fn deserialize_interval<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|raw| parse_interval(&raw).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_interval(" 6h "), Ok(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_interval("2d"), Ok(Duration::from_secs(2 * 86400)));
+    }
+
+    #[test]
+    fn rejects_bad_intervals() {
+        for raw in ["0s", "0d", "h", "10", "10w", "18446744073709551615d"] {
+            assert!(parse_interval(raw).is_err(), "{}", raw);
+        }
+    }
+}
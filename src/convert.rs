@@ -0,0 +1,624 @@
+use crate::Block;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Converts the children of a post body element into Markdown.
+pub fn html_to_markdown(element: ElementRef) -> String {
+    let mut out = String::new();
+    write_children(element, &mut out, 0);
+    normalize_blank_lines(&out)
+}
+
+fn write_children(element: ElementRef, out: &mut String, list_depth: usize) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => push_markdown_text(out, text),
+            Node::Element(_) => {
+                if let Some(child_element) = ElementRef::wrap(child) {
+                    write_element(child_element, out, list_depth);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_element(element: ElementRef, out: &mut String, list_depth: usize) {
+    let name = element.value().name();
+    match name {
+        "script" | "style" | "noscript" => {}
+        "br" => out.push_str("  \n"),
+        "hr" => {
+            block_break(out);
+            out.push_str("---");
+            block_break(out);
+        }
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse::<usize>().unwrap_or(1);
+            let text = inline_markdown(element, list_depth);
+            if !text.is_empty() {
+                block_break(out);
+                out.push_str(&"#".repeat(level));
+                out.push(' ');
+                out.push_str(&text);
+                block_break(out);
+            }
+        }
+        "p" | "div" | "table" | "tr" | "center" => {
+            block_break(out);
+            write_children(element, out, list_depth);
+            block_break(out);
+        }
+        "ul" | "ol" => {
+            block_break(out);
+            for (index, item) in element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|child| child.value().name() == "li")
+                .enumerate()
+            {
+                let marker = if name == "ol" {
+                    format!("{}. ", index + 1)
+                } else {
+                    "- ".to_string()
+                };
+                out.push_str(&"  ".repeat(list_depth));
+                out.push_str(&marker);
+                out.push_str(inline_markdown(item, list_depth + 1).trim_start());
+                out.push('\n');
+            }
+            block_break(out);
+        }
+        "blockquote" => {
+            let mut inner = String::new();
+            write_children(element, &mut inner, list_depth);
+            let quoted = normalize_blank_lines(&inner)
+                .lines()
+                .map(|line| format!("> {}", line).trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            block_break(out);
+            out.push_str(&quoted);
+            block_break(out);
+        }
+        "pre" => {
+            let code = element.text().collect::<String>();
+            block_break(out);
+            out.push_str("```\n");
+            out.push_str(code.trim_end());
+            out.push_str("\n```");
+            block_break(out);
+        }
+        "code" => wrap_inline(out, &element.text().collect::<String>(), "`"),
+        "strong" | "b" => wrap_inline(out, &inline_markdown(element, list_depth), "**"),
+        "em" | "i" => wrap_inline(out, &inline_markdown(element, list_depth), "*"),
+        "a" => {
+            let text = inline_markdown(element, list_depth);
+            match element.value().attr("href") {
+                Some(href) if !text.is_empty() => {
+                    out.push_str(&format!("[{}]({})", text, link_destination(href)));
+                }
+                _ => out.push_str(&text),
+            }
+        }
+        "img" => {
+            if let Some(src) = element.value().attr("src") {
+                let alt = element.value().attr("alt").unwrap_or("");
+                out.push_str(&format!(
+                    "![{}]({})",
+                    escape_markdown(alt),
+                    link_destination(&absolute_src(src))
+                ));
+            }
+        }
+        _ => write_children(element, out, list_depth),
+    }
+}
+
+fn inline_markdown(element: ElementRef, list_depth: usize) -> String {
+    let mut inner = String::new();
+    write_children(element, &mut inner, list_depth);
+    normalize_blank_lines(&inner)
+}
+
+fn wrap_inline(out: &mut String, text: &str, marker: &str) {
+    let trimmed = text.trim();
+    if !trimmed.is_empty() {
+        out.push_str(marker);
+        out.push_str(trimmed);
+        out.push_str(marker);
+    }
+}
+
+/// Appends text with runs of whitespace collapsed to a single space.
+fn push_text(out: &mut String, text: &str) {
+    push_collapsed(out, text, false);
+}
+
+/// Like `push_text`, but escapes characters Markdown would otherwise read
+/// as formatting.
+fn push_markdown_text(out: &mut String, text: &str) {
+    push_collapsed(out, text, true);
+}
+
+fn push_collapsed(out: &mut String, text: &str, escape: bool) {
+    let mut last_was_space = out.ends_with(char::is_whitespace) || out.is_empty();
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_was_space {
+                out.push(' ');
+                last_was_space = true;
+            }
+        } else {
+            let line_start = out.is_empty() || out.ends_with('\n');
+            if escape && (is_markdown_special(c) || (c == '#' && line_start)) {
+                out.push('\\');
+            }
+            out.push(c);
+            last_was_space = false;
+        }
+    }
+}
+
+/// Characters that start emphasis, links or code anywhere in a line.
+fn is_markdown_special(c: char) -> bool {
+    matches!(c, '\\' | '*' | '_' | '[' | ']' | '`')
+}
+
+/// Backslash-escapes text so it is shown literally rather than read as
+/// Markdown, including a `#` that would start a heading.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let line_start = out.is_empty() || out.ends_with('\n');
+        if is_markdown_special(c) || (c == '#' && line_start) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Link or image destination, wrapped in `<…>` when it contains spaces or
+/// parentheses that would otherwise end it early.
+fn link_destination(url: &str) -> String {
+    let url = url.trim().replace(['\n', '\r'], "");
+    if url.contains(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '<' | '>')) {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url
+    }
+}
+
+fn block_break(out: &mut String) {
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        while out.ends_with(' ') {
+            out.pop();
+        }
+        out.push_str(if out.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+}
+
+fn absolute_src(src: &str) -> String {
+    if src.starts_with("//") {
+        format!("http:{}", src)
+    } else {
+        src.to_string()
+    }
+}
+
+fn normalize_blank_lines(markdown: &str) -> String {
+    let mut result = String::new();
+    let mut blank_run = 0;
+    for line in markdown.lines() {
+        if line.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+            result.push('\n');
+        } else {
+            blank_run = 0;
+            result.push_str(line.trim_end());
+            if line.ends_with("  ") {
+                result.push_str("  ");
+            }
+            result.push('\n');
+        }
+    }
+    result.trim().to_string()
+}
+
+/// Splits the children of a post body element into structural blocks.
+pub fn html_to_blocks(element: ElementRef) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph = String::new();
+    collect_blocks(element, &mut blocks, &mut paragraph);
+    flush_paragraph(&mut blocks, &mut paragraph);
+    blocks
+}
+
+fn collect_blocks(element: ElementRef, blocks: &mut Vec<Block>, paragraph: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => push_text(paragraph, text),
+            Node::Element(_) => {
+                if let Some(child_element) = ElementRef::wrap(child) {
+                    collect_block(child_element, blocks, paragraph);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_block(element: ElementRef, blocks: &mut Vec<Block>, paragraph: &mut String) {
+    let name = element.value().name();
+    match name {
+        "script" | "style" | "noscript" => {}
+        // Blogger's editor separates paragraphs with <br><br> as often as <p>
+        "br" => {
+            if paragraph.ends_with('\n') {
+                flush_paragraph(blocks, paragraph);
+            } else {
+                while paragraph.ends_with(' ') {
+                    paragraph.pop();
+                }
+                paragraph.push('\n');
+            }
+        }
+        "hr" => flush_paragraph(blocks, paragraph),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            flush_paragraph(blocks, paragraph);
+            let text = inline_text(element);
+            if !text.is_empty() {
+                blocks.push(Block::Heading {
+                    level: name[1..].parse().unwrap_or(1),
+                    text,
+                });
+            }
+        }
+        "ul" | "ol" => {
+            flush_paragraph(blocks, paragraph);
+            let items: Vec<String> = element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|child| child.value().name() == "li")
+                .map(inline_text)
+                .filter(|item| !item.is_empty())
+                .collect();
+            if !items.is_empty() {
+                blocks.push(Block::List {
+                    ordered: name == "ol",
+                    items,
+                });
+            }
+        }
+        "blockquote" => {
+            flush_paragraph(blocks, paragraph);
+            let text = html_to_blocks(element)
+                .iter()
+                .filter_map(block_text)
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            if !text.is_empty() {
+                blocks.push(Block::Blockquote { text });
+            }
+        }
+        "pre" => {
+            flush_paragraph(blocks, paragraph);
+            let text = element.text().collect::<String>();
+            let text = text.trim_end().trim_start_matches('\n');
+            if !text.is_empty() {
+                blocks.push(Block::Code {
+                    text: text.to_string(),
+                });
+            }
+        }
+        "img" => {
+            if let Some(src) = element.value().attr("src") {
+                flush_paragraph(blocks, paragraph);
+                blocks.push(Block::Image {
+                    src: absolute_src(src),
+                    alt: element
+                        .value()
+                        .attr("alt")
+                        .map(str::trim)
+                        .filter(|alt| !alt.is_empty())
+                        .map(str::to_string),
+                    caption: None,
+                });
+            }
+        }
+        "table" | "figure" => {
+            flush_paragraph(blocks, paragraph);
+            let start = blocks.len();
+            collect_blocks(element, blocks, paragraph);
+            flush_paragraph(blocks, paragraph);
+            if let Some(caption) = caption_text(element) {
+                caption_image(blocks, start, caption);
+            }
+        }
+        "p" | "div" | "tr" | "center" | "section" | "article" => {
+            flush_paragraph(blocks, paragraph);
+            collect_blocks(element, blocks, paragraph);
+            flush_paragraph(blocks, paragraph);
+        }
+        _ => collect_blocks(element, blocks, paragraph),
+    }
+}
+
+/// Blogger wraps captioned images in a `table.tr-caption-container` with the
+/// caption in a `td.tr-caption` cell; other editors use `<figcaption>`.
+fn caption_text(element: ElementRef) -> Option<String> {
+    let selector = Selector::parse("td.tr-caption, figcaption").ok()?;
+    element
+        .select(&selector)
+        .map(inline_text)
+        .find(|caption| !caption.is_empty())
+}
+
+/// Moves a caption that was collected as a paragraph onto the first image
+/// collected since `start`.
+fn caption_image(blocks: &mut Vec<Block>, start: usize, caption: String) {
+    if !blocks[start..]
+        .iter()
+        .any(|block| matches!(block, Block::Image { .. }))
+    {
+        return;
+    }
+    let caption_paragraph = blocks[start..].iter().position(|block| {
+        matches!(block, Block::Paragraph { text }
+            if text.split_whitespace().eq(caption.split_whitespace()))
+    });
+    if let Some(index) = caption_paragraph {
+        blocks.remove(start + index);
+    }
+    if let Some(Block::Image { caption: slot, .. }) = blocks[start..]
+        .iter_mut()
+        .find(|block| matches!(block, Block::Image { .. }))
+    {
+        *slot = Some(caption);
+    }
+}
+
+fn flush_paragraph(blocks: &mut Vec<Block>, paragraph: &mut String) {
+    let text = paragraph.trim();
+    if !text.is_empty() {
+        blocks.push(Block::Paragraph {
+            text: text.to_string(),
+        });
+    }
+    paragraph.clear();
+}
+
+fn inline_text(element: ElementRef) -> String {
+    let mut out = String::new();
+    for node in element.descendants() {
+        match node.value() {
+            Node::Text(text) => push_text(&mut out, text),
+            Node::Element(child) if child.name() == "br" => push_text(&mut out, " "),
+            _ => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// The text of a block, or None for images.
+pub fn block_text(block: &Block) -> Option<String> {
+    match block {
+        Block::Paragraph { text }
+        | Block::Heading { text, .. }
+        | Block::Blockquote { text }
+        | Block::Code { text } => Some(text.clone()),
+        Block::List { items, .. } => Some(items.join("\n")),
+        Block::Image { .. } => None,
+    }
+}
+
+/// Renders blocks as HTML that is also well-formed XHTML.
+pub fn blocks_to_html(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph { text } => {
+                out.push_str(&format!("<p>{}</p>\n", escape_with_breaks(text)));
+            }
+            Block::Heading { level, text } => {
+                // The page title is the only <h1>
+                let level = (*level).clamp(2, 6);
+                out.push_str(&format!("<h{0}>{1}</h{0}>\n", level, escape_xml(text)));
+            }
+            Block::List { ordered, items } => {
+                let tag = if *ordered { "ol" } else { "ul" };
+                out.push_str(&format!("<{}>\n", tag));
+                for item in items {
+                    out.push_str(&format!("<li>{}</li>\n", escape_xml(item)));
+                }
+                out.push_str(&format!("</{}>\n", tag));
+            }
+            Block::Image { src, alt, caption } => {
+                let image = format!(
+                    "<img src=\"{}\" alt=\"{}\" />",
+                    escape_xml(src),
+                    escape_xml(alt.as_deref().unwrap_or(""))
+                );
+                match caption {
+                    Some(caption) => out.push_str(&format!(
+                        "<figure>{}<figcaption>{}</figcaption></figure>\n",
+                        image,
+                        escape_xml(caption)
+                    )),
+                    None => out.push_str(&format!("<p>{}</p>\n", image)),
+                }
+            }
+            Block::Blockquote { text } => {
+                out.push_str("<blockquote>\n");
+                for paragraph in text.split("\n\n") {
+                    out.push_str(&format!("<p>{}</p>\n", escape_with_breaks(paragraph)));
+                }
+                out.push_str("</blockquote>\n");
+            }
+            Block::Code { text } => {
+                out.push_str(&format!("<pre>{}</pre>\n", escape_xml(text)));
+            }
+        }
+    }
+    out
+}
+
+fn escape_with_breaks(text: &str) -> String {
+    escape_xml(text).replace('\n', "<br />\n")
+}
+
+/// Renders blocks as Markdown.
+pub fn blocks_to_markdown(blocks: &[Block]) -> String {
+    let mut parts = Vec::new();
+    for block in blocks {
+        parts.push(match block {
+            Block::Paragraph { text } => escape_markdown(text).replace('\n', "  \n"),
+            Block::Heading { level, text } => {
+                format!("{} {}", "#".repeat(*level as usize), escape_markdown(text))
+            }
+            Block::List { ordered, items } => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    if *ordered {
+                        format!("{}. {}", index + 1, escape_markdown(item))
+                    } else {
+                        format!("- {}", escape_markdown(item))
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Image { src, alt, caption } => {
+                let image = format!(
+                    "![{}]({})",
+                    escape_markdown(alt.as_deref().unwrap_or("")),
+                    link_destination(src)
+                );
+                match caption {
+                    Some(caption) => format!("{}  \n*{}*", image, escape_markdown(caption)),
+                    None => image,
+                }
+            }
+            Block::Blockquote { text } => escape_markdown(text)
+                .lines()
+                .map(|line| format!("> {}", line).trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Code { text } => format!("```\n{}\n```", text),
+        });
+    }
+    parts.join("\n\n")
+}
+
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Re-serializes an HTML fragment as well-formed XHTML so it can be embedded
+/// in formats with strict XML parsers such as EPUB.
+pub fn html_to_xhtml(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut out = String::new();
+    write_xhtml_children(fragment.root_element(), &mut out);
+    out
+}
+
+fn write_xhtml_children(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape_xml(text)),
+            Node::Element(_) => {
+                if let Some(child_element) = ElementRef::wrap(child) {
+                    write_xhtml_element(child_element, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_xhtml_element(element: ElementRef, out: &mut String) {
+    let name = element.value().name();
+    if matches!(name, "script" | "style") {
+        return;
+    }
+
+    out.push('<');
+    out.push_str(name);
+    for (attr, value) in element.value().attrs() {
+        if attr.contains(':') {
+            continue;
+        }
+        out.push_str(&format!(" {}=\"{}\"", attr, escape_xml(value)));
+    }
+
+    if VOID_ELEMENTS.contains(&name) {
+        out.push_str(" />");
+    } else {
+        out.push('>');
+        write_xhtml_children(element, out);
+        out.push_str(&format!("</{}>", name));
+    }
+}
+
+/// Decodes an HTML fragment (such as a scraped title) to its plain text.
+pub fn html_to_text(html: &str) -> String {
+    Html::parse_fragment(html)
+        .root_element()
+        .text()
+        .collect::<String>()
+}
+
+/// Escapes text for use inside XML/XHTML content and attribute values.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "<p>snake_case and *stars* [x]</p><h2>#1_pick</h2>";
+
+    #[test]
+    fn blocks_keep_plain_text_and_markdown_escapes_once() {
+        let fragment = Html::parse_fragment(BODY);
+        let blocks = html_to_blocks(fragment.root_element());
+        assert_eq!(
+            blocks,
+            [
+                Block::Paragraph {
+                    text: "snake_case and *stars* [x]".to_string()
+                },
+                Block::Heading {
+                    level: 2,
+                    text: "#1_pick".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            blocks_to_markdown(&blocks),
+            "snake\\_case and \\*stars\\* \\[x\\]\n\n## \\#1\\_pick"
+        );
+        assert_eq!(
+            blocks_to_html(&blocks),
+            "<p>snake_case and *stars* [x]</p>\n<h2>#1_pick</h2>\n"
+        );
+    }
+
+    #[test]
+    fn html_to_markdown_escapes_text() {
+        let fragment = Html::parse_fragment(BODY);
+        assert_eq!(
+            html_to_markdown(fragment.root_element()),
+            "snake\\_case and \\*stars\\* \\[x\\]\n\n## \\#1\\_pick"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use crate::DateLocale;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use scraper::{Html, Selector};
+
+/// Month names for each supported language, January first. Abbreviations are
+/// matched as prefixes of these, so only the full names are listed.
+const MONTHS: &[(DateLocale, [&str; 12])] = &[
+    (
+        DateLocale::En,
+        [
+            "january",
+            "february",
+            "march",
+            "april",
+            "may",
+            "june",
+            "july",
+            "august",
+            "september",
+            "october",
+            "november",
+            "december",
+        ],
+    ),
+    (
+        DateLocale::Es,
+        [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+    ),
+    (
+        DateLocale::Pt,
+        [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+    ),
+    (
+        DateLocale::Fr,
+        [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+    ),
+    (
+        DateLocale::De,
+        [
+            "januar",
+            "februar",
+            "märz",
+            "april",
+            "mai",
+            "juni",
+            "juli",
+            "august",
+            "september",
+            "oktober",
+            "november",
+            "dezember",
+        ],
+    ),
+    (
+        DateLocale::It,
+        [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+    ),
+    (
+        DateLocale::Nl,
+        [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+    ),
+];
+
+/// Parses the text of a Blogger date header in any of the formats the
+/// template settings offer, e.g. "Sunday, 3 March 2024", "Sunday, March 3,
+/// 2024", "domingo, 3 de marzo de 2024", "3/3/2024" or "2024年3月3日".
+///
+/// Numeric dates are read month first for English and day first otherwise,
+/// unless only one reading is a valid date.
+pub fn parse_date_header(text: &str, locale: DateLocale) -> Option<NaiveDate> {
+    let tokens = tokenize(text);
+    let numbers: Vec<&str> = tokens
+        .iter()
+        .copied()
+        .filter(|token| token.starts_with(|c: char| c.is_ascii_digit()))
+        .collect();
+
+    let month = tokens
+        .iter()
+        .find_map(|token| month_number(token, locale, false))
+        .or_else(|| {
+            // Weekdays come before the month, so the last abbreviation wins
+            tokens
+                .iter()
+                .rev()
+                .find_map(|token| month_number(token, locale, true))
+        });
+    if let Some(month) = month {
+        let year = numbers.iter().find(|n| n.len() == 4)?.parse().ok()?;
+        let day = numbers
+            .iter()
+            .filter(|n| n.len() <= 2)
+            .find_map(|n| n.parse().ok())?;
+        return NaiveDate::from_ymd_opt(year, month, day);
+    }
+
+    let [a, b, c] = numbers.as_slice() else {
+        return None;
+    };
+    if a.len() == 4 {
+        return NaiveDate::from_ymd_opt(a.parse().ok()?, b.parse().ok()?, c.parse().ok()?);
+    }
+    if c.len() != 4 {
+        return None;
+    }
+    let (year, a, b) = (c.parse().ok()?, a.parse().ok()?, b.parse().ok()?);
+    let (month, day) = if locale.month_first() { (a, b) } else { (b, a) };
+    NaiveDate::from_ymd_opt(year, month, day).or_else(|| NaiveDate::from_ymd_opt(year, day, month))
+}
+
+/// Elements that carry a post's publication timestamp in machine-readable
+/// form: schema.org markup, the `abbr.published` most Blogger templates put
+/// in the post footer, and Open Graph article metadata.
+const PUBLISHED_MARKUP: &str = "[itemprop=datePublished], abbr.published, time.published, \
+     meta[property='article:published_time']";
+
+/// The same for the time a post was last edited.
+const UPDATED_MARKUP: &str = "[itemprop=dateModified], abbr.updated, time.updated, \
+     meta[property='article:modified_time']";
+
+/// Publication date from the timestamp in the post's markup, for templates
+/// whose date header is missing or unreadable.
+pub fn published_date(document: &Html) -> Option<NaiveDate> {
+    markup_values(document, PUBLISHED_MARKUP).find_map(|timestamp| {
+        DateTime::parse_from_rfc3339(timestamp)
+            .map(|time| time.date_naive())
+            .ok()
+            .or_else(|| NaiveDate::parse_from_str(timestamp.get(..10)?, "%Y-%m-%d").ok())
+    })
+}
+
+/// Full publication timestamp from the post's markup, with its UTC offset.
+pub fn published_time(document: &Html) -> Option<DateTime<FixedOffset>> {
+    markup_values(document, PUBLISHED_MARKUP)
+        .find_map(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+}
+
+/// Timestamp of the post's last edit from its markup, with its UTC offset.
+pub fn updated_time(document: &Html) -> Option<DateTime<FixedOffset>> {
+    markup_values(document, UPDATED_MARKUP)
+        .find_map(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+}
+
+fn markup_values<'a>(document: &'a Html, selectors: &str) -> impl Iterator<Item = &'a str> {
+    let selector = Selector::parse(selectors).ok();
+    selector
+        .into_iter()
+        .flat_map(|selector| document.select(&selector).collect::<Vec<_>>())
+        .filter_map(|element| {
+            let value = element.value();
+            value
+                .attr("content")
+                .or_else(|| value.attr("datetime"))
+                .or_else(|| value.attr("title"))
+                .map(str::trim)
+        })
+}
+
+/// Splits text into runs of digits and runs of letters, dropping everything
+/// else, so "3rd" and "3." both yield "3" and CJK date markers separate numbers.
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut digits = false;
+    for (i, c) in text.char_indices() {
+        let kind = if c.is_ascii_digit() {
+            Some(true)
+        } else if c.is_alphabetic() {
+            Some(false)
+        } else {
+            None
+        };
+        match (start, kind) {
+            (Some(s), Some(is_digit)) if is_digit != digits => {
+                tokens.push(&text[s..i]);
+                start = Some(i);
+                digits = is_digit;
+            }
+            (Some(s), None) => {
+                tokens.push(&text[s..i]);
+                start = None;
+            }
+            (None, Some(is_digit)) => {
+                start = Some(i);
+                digits = is_digit;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&text[s..]);
+    }
+    tokens
+}
+
+/// Number of the month a word names. With `abbreviated`, also accepts a
+/// prefix of at least three letters that matches only one month.
+fn month_number(word: &str, locale: DateLocale, abbreviated: bool) -> Option<u32> {
+    let word = word.to_lowercase();
+    if word.chars().count() < 3 {
+        return None;
+    }
+    MONTHS
+        .iter()
+        .filter(|(table_locale, _)| locale == DateLocale::Auto || locale == *table_locale)
+        .find_map(|(_, months)| {
+            if let Some(i) = months.iter().position(|month| *month == word) {
+                return Some(i as u32 + 1);
+            }
+            if !abbreviated {
+                return None;
+            }
+            let mut matches = months
+                .iter()
+                .enumerate()
+                .filter(|(_, month)| month.starts_with(&word));
+            match (matches.next(), matches.next()) {
+                (Some((i, _)), None) => Some(i as u32 + 1),
+                _ => None,
+            }
+        })
+}
+
+impl DateLocale {
+    fn month_first(self) -> bool {
+        matches!(self, DateLocale::Auto | DateLocale::En)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn parses_month_names() {
+        let auto = DateLocale::Auto;
+        assert_eq!(
+            parse_date_header("Sunday, 3 March 2024", auto),
+            date(2024, 3, 3)
+        );
+        assert_eq!(
+            parse_date_header("Sunday, March 3, 2024", auto),
+            date(2024, 3, 3)
+        );
+        assert_eq!(
+            parse_date_header("Sun, Mar 3, 2024", auto),
+            date(2024, 3, 3)
+        );
+        assert_eq!(
+            parse_date_header("domingo, 3 de marzo de 2024", DateLocale::Es),
+            date(2024, 3, 3)
+        );
+        assert_eq!(
+            parse_date_header("dimanche 3 mars 2024", DateLocale::Fr),
+            date(2024, 3, 3)
+        );
+        assert_eq!(
+            parse_date_header("Sonntag, 3. März 2024", DateLocale::De),
+            date(2024, 3, 3)
+        );
+        assert_eq!(
+            parse_date_header("domingo, 3 de marzo de 2024", auto),
+            date(2024, 3, 3)
+        );
+    }
+
+    #[test]
+    fn numeric_order_follows_locale() {
+        assert_eq!(
+            parse_date_header("3/4/2024", DateLocale::En),
+            date(2024, 3, 4)
+        );
+        assert_eq!(
+            parse_date_header("3/4/2024", DateLocale::De),
+            date(2024, 4, 3)
+        );
+        // Only one order is a valid date, so the locale's preference is overridden
+        assert_eq!(
+            parse_date_header("25/3/2024", DateLocale::En),
+            date(2024, 3, 25)
+        );
+        assert_eq!(
+            parse_date_header("3/25/2024", DateLocale::Fr),
+            date(2024, 3, 25)
+        );
+    }
+
+    #[test]
+    fn parses_year_first_dates() {
+        let auto = DateLocale::Auto;
+        assert_eq!(parse_date_header("2024-03-04", auto), date(2024, 3, 4));
+        assert_eq!(parse_date_header("2024年3月4日", auto), date(2024, 3, 4));
+    }
+
+    #[test]
+    fn rejects_text_without_a_date() {
+        assert_eq!(parse_date_header("", DateLocale::Auto), None);
+        assert_eq!(
+            parse_date_header("Labels: rust, blogging", DateLocale::Auto),
+            None
+        );
+        assert_eq!(parse_date_header("31 February 2024", DateLocale::En), None);
+    }
+}
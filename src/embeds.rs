@@ -0,0 +1,103 @@
+use crate::fetcher::Fetcher;
+use crate::Embed;
+use reqwest::Url;
+use scraper::{ElementRef, Selector};
+use tracing::warn;
+
+const VIMEO_OEMBED_API: &str = "https://vimeo.com/api/oembed.json";
+
+/// Finds the videos and other frames embedded in a post body: iframes, and
+/// the `<object>`/`<embed>` players older Blogger posts used for YouTube.
+pub fn extract_embeds(body: ElementRef) -> Vec<Embed> {
+    let Ok(selector) = Selector::parse("iframe, embed, object") else {
+        return Vec::new();
+    };
+    let mut embeds: Vec<Embed> = Vec::new();
+    for element in body.select(&selector) {
+        let value = element.value();
+        let Some(src) = value
+            .attr("src")
+            .or_else(|| value.attr("data-src"))
+            .or_else(|| value.attr("data"))
+        else {
+            continue;
+        };
+        let Some(embed) = parse_embed(src) else {
+            continue;
+        };
+        // An <object> usually wraps an <embed> of the same player
+        if !embeds.iter().any(|seen| seen.url == embed.url) {
+            embeds.push(embed);
+        }
+    }
+    embeds
+}
+
+/// Identifies the provider and video id of an embedded player URL.
+fn parse_embed(src: &str) -> Option<Embed> {
+    let absolute = if src.starts_with("//") {
+        format!("https:{}", src)
+    } else {
+        src.to_string()
+    };
+    let url = Url::parse(&absolute).ok()?;
+    let host = url.host_str()?.trim_start_matches("www.").to_string();
+    let mut segments = url.path_segments().into_iter().flatten();
+
+    let (provider, video_id) = match host.as_str() {
+        "youtube.com" | "youtube-nocookie.com" | "m.youtube.com" => {
+            let id = match segments.next() {
+                Some("embed") | Some("v") => segments.next().map(str::to_string),
+                Some("watch") => url
+                    .query_pairs()
+                    .find(|(key, _)| key == "v")
+                    .map(|(_, value)| value.into_owned()),
+                _ => None,
+            };
+            ("youtube".to_string(), id)
+        }
+        "youtu.be" => ("youtube".to_string(), segments.next().map(str::to_string)),
+        "player.vimeo.com" | "vimeo.com" => {
+            let id = segments.find(|segment| segment.chars().all(|c| c.is_ascii_digit()));
+            ("vimeo".to_string(), id.map(str::to_string))
+        }
+        _ => (host, None),
+    };
+
+    Some(Embed {
+        provider,
+        video_id: video_id.filter(|id| !id.is_empty()),
+        url: absolute,
+        thumbnail: None,
+    })
+}
+
+/// Fills in preview images for YouTube and Vimeo embeds. YouTube thumbnails
+/// live at a fixed address; Vimeo's are looked up through its oEmbed API.
+pub fn resolve_thumbnails(fetcher: &Fetcher, embeds: &mut [Embed]) {
+    for embed in embeds.iter_mut().filter(|embed| embed.thumbnail.is_none()) {
+        let Some(id) = &embed.video_id else {
+            continue;
+        };
+        match embed.provider.as_str() {
+            "youtube" => {
+                embed.thumbnail = Some(format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", id));
+            }
+            "vimeo" => {
+                let api_url = format!("{}?url=https://vimeo.com/{}", VIMEO_OEMBED_API, id);
+                match fetcher
+                    .fetch_html(&api_url)
+                    .and_then(|json| Ok(serde_json::from_str::<serde_json::Value>(&json)?))
+                {
+                    Ok(oembed) => {
+                        embed.thumbnail = oembed["thumbnail_url"].as_str().map(str::to_string);
+                    }
+                    Err(e) => {
+                        warn!(url = %embed.url, error = %e, "failed to look up video thumbnail")
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use reqwest::StatusCode;
+use scraper::error::SelectorErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScrapeError {
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("server responded with {status} (retry after {}s)", retry_after.as_secs())]
+    RateLimited {
+        status: StatusCode,
+        retry_after: Duration,
+    },
+
+    #[error("{url} no longer exists ({status})")]
+    Gone { url: String, status: StatusCode },
+
+    #[error("{url} responded with {status}")]
+    Http { url: String, status: StatusCode },
+
+    #[error("failed to parse {url}: {reason}")]
+    Parse { url: String, reason: String },
+
+    #[error("invalid selector: {0}")]
+    Selector(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("binary backup error: {0}")]
+    Binary(String),
+
+    #[error("{url} was written with schema version {found}, but this build only reads up to {supported}")]
+    UnsupportedSchema {
+        url: String,
+        found: u32,
+        supported: u32,
+    },
+
+    #[error("failed to read {}: {source}", path.display())]
+    Backup {
+        path: PathBuf,
+        source: Box<ScrapeError>,
+    },
+
+    #[error("EPUB error: {0}")]
+    Epub(#[from] epub_builder::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    #[error("PDF error: {0}")]
+    Pdf(#[from] printpdf::Error),
+
+    #[error("search index error: {0}")]
+    Index(#[from] tantivy::TantivyError),
+
+    #[error("failed to send notification: {0}")]
+    Notification(String),
+
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    #[error("failed to install interrupt handler: {0}")]
+    Signal(#[from] ctrlc::Error),
+
+    #[error("a worker thread panicked while holding shared state")]
+    Poisoned,
+}
+
+impl ScrapeError {
+    pub fn parse(url: &str, reason: &str) -> Self {
+        ScrapeError::Parse {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Transient failures worth another attempt, including server errors.
+    /// Pages that were fetched but could not be parsed will fail the same way
+    /// again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ScrapeError::Network(_) | ScrapeError::RateLimited { .. } | ScrapeError::Io(_) => true,
+            ScrapeError::Http { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ScrapeError::Network(e) if e.is_timeout())
+    }
+
+    /// Process exit code following the BSD sysexits conventions.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ScrapeError::Backup { source, .. } => source.exit_code(),
+            ScrapeError::Network(_)
+            | ScrapeError::RateLimited { .. }
+            | ScrapeError::Gone { .. }
+            | ScrapeError::Http { .. }
+            | ScrapeError::Notification(_) => 69,
+            ScrapeError::Parse { .. }
+            | ScrapeError::Json(_)
+            | ScrapeError::Binary(_)
+            | ScrapeError::UnsupportedSchema { .. } => 65,
+            ScrapeError::Io(_)
+            | ScrapeError::Epub(_)
+            | ScrapeError::Csv(_)
+            | ScrapeError::Parquet(_)
+            | ScrapeError::Pdf(_)
+            | ScrapeError::Index(_) => 74,
+            ScrapeError::Config(_) | ScrapeError::Selector(_) => 78,
+            ScrapeError::Signal(_) | ScrapeError::Poisoned => 70,
+        }
+    }
+}
+
+impl From<SelectorErrorKind<'_>> for ScrapeError {
+    fn from(e: SelectorErrorKind<'_>) -> Self {
+        ScrapeError::Selector(e.to_string())
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for ScrapeError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        ScrapeError::Poisoned
+    }
+}
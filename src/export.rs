@@ -0,0 +1,745 @@
+use super::convert;
+use super::fetcher::Fetcher;
+use super::helpers;
+use crate::error::ScrapeError;
+use crate::{Block, Post};
+use chrono::{Datelike, NaiveDate};
+use epub_builder::{EpubBuilder, EpubContent, ReferenceType, ZipLibrary};
+use parquet::basic::{Compression, GzipLevel};
+use parquet::data_type::{ByteArray, ByteArrayType, Int32Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use regex::Regex;
+use scraper::Html;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+
+const PARQUET_ROWS_PER_GROUP: usize = 10_000;
+const PARQUET_SCHEMA: &str = "
+message post {
+    OPTIONAL BYTE_ARRAY id (UTF8);
+    REQUIRED BYTE_ARRAY title (UTF8);
+    REQUIRED BYTE_ARRAY url (UTF8);
+    OPTIONAL BYTE_ARRAY date_raw (UTF8);
+    OPTIONAL INT32 date (DATE);
+    REQUIRED group labels (LIST) {
+        REPEATED group list {
+            REQUIRED BYTE_ARRAY element (UTF8);
+        }
+    }
+    REQUIRED group images (LIST) {
+        REPEATED group list {
+            REQUIRED BYTE_ARRAY element (UTF8);
+        }
+    }
+    REQUIRED INT32 word_count;
+    REQUIRED INT32 comment_count;
+    REQUIRED BYTE_ARRAY content (UTF8);
+    OPTIONAL BYTE_ARRAY content_html (UTF8);
+}
+";
+
+/// Values for one Parquet column, with the definition and repetition levels
+/// needed for optional and list columns.
+enum ParquetColumn {
+    Strings {
+        values: Vec<ByteArray>,
+        def_levels: Option<Vec<i16>>,
+        rep_levels: Option<Vec<i16>>,
+    },
+    Ints {
+        values: Vec<i32>,
+        def_levels: Option<Vec<i16>>,
+    },
+}
+
+/// Builds an EPUB titled `title`, or the blog's host name when none is given.
+/// The author is whoever wrote the most posts.
+pub fn export_epub(
+    fetcher: &Fetcher,
+    input: &Path,
+    output: &Path,
+    title: Option<&str>,
+) -> Result<(), ScrapeError> {
+    let posts = helpers::read_posts_from_file(input)?;
+    let mut ordered: Vec<&Post> = posts.iter().collect();
+    helpers::sort_backup_asc(&mut ordered)?;
+
+    println!(
+        "Building EPUB from {} posts found in {}",
+        ordered.len(),
+        input.display()
+    );
+
+    let mut builder = EpubBuilder::new(ZipLibrary::new()?)?;
+    let blog_name = helpers::blog_name(&posts);
+    builder.set_title(title.unwrap_or(&blog_name));
+    builder.add_author(main_author(&posts).unwrap_or(&blog_name));
+    builder.inline_toc();
+
+    let mut embedded_images: HashMap<String, String> = HashMap::new();
+    for (index, post) in ordered.iter().enumerate() {
+        let mut local_images = Vec::new();
+        for image_url in &post.images {
+            if let Some(local_path) = embedded_images.get(image_url) {
+                local_images.push((image_url.clone(), local_path.clone()));
+                continue;
+            }
+
+            match fetcher.fetch_bytes(image_url) {
+                Ok((bytes, content_type)) => {
+                    let mime = content_type
+                        .filter(|ct| ct.starts_with("image/"))
+                        .unwrap_or_else(|| guess_image_mime(image_url));
+                    let local_path = format!(
+                        "images/{}.{}",
+                        embedded_images.len(),
+                        image_extension(&mime)
+                    );
+                    builder.add_resource(&local_path, bytes.as_slice(), mime.as_str())?;
+                    embedded_images.insert(image_url.clone(), local_path.clone());
+                    local_images.push((image_url.clone(), local_path));
+                }
+                Err(e) => {
+                    eprintln!("Failed to embed image {}: {}", image_url, e);
+                }
+            }
+        }
+
+        let title = convert::html_to_text(&post.title);
+        let chapter = render_epub_chapter(post, &title, &local_images);
+        builder.add_content(
+            EpubContent::new(format!("post_{}.xhtml", index), chapter.as_bytes())
+                .title(title.as_str())
+                .reftype(ReferenceType::Text),
+        )?;
+    }
+
+    let output_file = File::create(output)?;
+    builder.generate(output_file)?;
+    println!(
+        "EPUB with {} chapters and {} images written to {}",
+        ordered.len(),
+        embedded_images.len(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn render_epub_chapter(post: &Post, title: &str, local_images: &[(String, String)]) -> String {
+    let mut body = String::new();
+    body.push_str(&format!("<h1>{}</h1>\n", convert::escape_xml(title)));
+    if let Some(date) = &post.date {
+        body.push_str(&format!("<p class=\"date\">{}</p>\n", date));
+    }
+
+    let xhtml = match &post.content_html {
+        Some(html) => convert::html_to_xhtml(html),
+        None => convert::blocks_to_html(&helpers::post_blocks(post)),
+    };
+    let (xhtml, unplaced_images) = localize_images(&xhtml, local_images);
+    body.push_str(&xhtml);
+
+    for local in unplaced_images {
+        body.push_str(&format!("<p><img src=\"{}\" alt=\"\" /></p>\n", local));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\">\n\
+         <head><title>{}</title></head>\n\
+         <body>\n{}</body>\n</html>\n",
+        convert::escape_xml(title),
+        body
+    )
+}
+
+/// The author named on the most posts, if any post names one.
+fn main_author(posts: &[Post]) -> Option<&str> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for author in posts.iter().filter_map(|post| post.author.as_deref()) {
+        *counts.entry(author).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))
+        .map(|(author, _)| author)
+}
+
+/// Writes one Markdown file per post with YAML front matter, laid out as a
+/// Hugo `content/posts/` directory or, with `jekyll`, a Jekyll `_posts/`
+/// directory whose file names carry the publication date.
+pub fn export_markdown_site(input: &Path, dir: &Path, jekyll: bool) -> Result<(), ScrapeError> {
+    let mut posts = helpers::read_posts_from_file(input)?;
+    helpers::sort_backup(&mut posts)?;
+    let posts_dir = if jekyll {
+        dir.join("_posts")
+    } else {
+        dir.join("content").join("posts")
+    };
+    fs::create_dir_all(&posts_dir)?;
+
+    let mut written = 0;
+    for (post, slug) in posts.iter().zip(unique_slugs(&posts)) {
+        let date = helpers::post_date(post);
+        let file_name = match (jekyll, date) {
+            (false, _) => format!("{}.md", slug),
+            (true, Some(date)) => format!("{}-{}.md", date.format("%Y-%m-%d"), slug),
+            (true, None) => {
+                eprintln!("Skipping {}: Jekyll posts need a date", post.URL);
+                continue;
+            }
+        };
+
+        let mut out = front_matter(post, date)?;
+        out.push_str(post_markdown(post).trim());
+        out.push('\n');
+
+        fs::write(posts_dir.join(file_name), out)?;
+        written += 1;
+    }
+
+    println!(
+        "{} Markdown posts written to {}",
+        written,
+        posts_dir.display()
+    );
+    Ok(())
+}
+
+/// Writes one row of metadata per post, newest first, for spreadsheets.
+pub fn export_csv(input: &Path, output: &Path, tsv: bool) -> Result<(), ScrapeError> {
+    let mut posts = helpers::read_posts_from_file(input)?;
+    helpers::sort_backup(&mut posts)?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(if tsv { b'\t' } else { b',' })
+        .from_path(output)?;
+    writer.write_record([
+        "id",
+        "title",
+        "date",
+        "url",
+        "word_count",
+        "labels",
+        "image_count",
+    ])?;
+    for post in &posts {
+        writer.write_record([
+            post.id.as_deref().unwrap_or(""),
+            &convert::html_to_text(&post.title),
+            &post.date.map(|date| date.to_string()).unwrap_or_default(),
+            &post.URL,
+            &post.word_count.to_string(),
+            &post.labels.join("; "),
+            &post.images.len().to_string(),
+        ])?;
+    }
+    writer.flush()?;
+
+    println!("{} rows written to {}", posts.len(), output.display());
+    Ok(())
+}
+
+/// Writes post metadata and content to a gzip-compressed Parquet file that
+/// DuckDB, Polars and similar tools can query directly.
+pub fn export_parquet(input: &Path, output: &Path) -> Result<(), ScrapeError> {
+    let mut posts = helpers::read_posts_from_file(input)?;
+    helpers::sort_backup(&mut posts)?;
+
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::GZIP(GzipLevel::default()))
+        .build();
+    let mut writer =
+        SerializedFileWriter::new(File::create(output)?, schema, Arc::new(properties))?;
+
+    for chunk in posts.chunks(PARQUET_ROWS_PER_GROUP) {
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+        let titles: Vec<String> = chunk
+            .iter()
+            .map(|post| convert::html_to_text(&post.title))
+            .collect();
+        let columns = [
+            optional_strings(chunk.iter().map(|post| post.id.as_deref())),
+            required_strings(titles.iter().map(String::as_str)),
+            required_strings(chunk.iter().map(|post| post.URL.as_str())),
+            optional_strings(chunk.iter().map(|post| post.date_raw.as_deref())),
+            optional_ints(
+                chunk
+                    .iter()
+                    .map(|post| post.date.map(|date| (date - epoch).num_days() as i32)),
+            ),
+            string_lists(chunk.iter().map(|post| post.labels.iter().collect())),
+            string_lists(chunk.iter().map(|post| post.images.iter().collect())),
+            required_ints(chunk.iter().map(|post| post.word_count as i32)),
+            required_ints(chunk.iter().map(|post| post.comments.len() as i32)),
+            required_strings(chunk.iter().map(|post| post.content.as_str())),
+            optional_strings(chunk.iter().map(|post| post.content_html.as_deref())),
+        ];
+
+        let mut row_group = writer.next_row_group()?;
+        let mut columns = columns.into_iter();
+        while let Some(mut column_writer) = row_group.next_column()? {
+            match columns.next() {
+                Some(ParquetColumn::Strings {
+                    values,
+                    def_levels,
+                    rep_levels,
+                }) => {
+                    column_writer.typed::<ByteArrayType>().write_batch(
+                        &values,
+                        def_levels.as_deref(),
+                        rep_levels.as_deref(),
+                    )?;
+                }
+                Some(ParquetColumn::Ints { values, def_levels }) => {
+                    column_writer.typed::<Int32Type>().write_batch(
+                        &values,
+                        def_levels.as_deref(),
+                        None,
+                    )?;
+                }
+                None => {}
+            }
+            column_writer.close()?;
+        }
+        row_group.close()?;
+    }
+    writer.close()?;
+
+    println!("{} posts written to {}", posts.len(), output.display());
+    Ok(())
+}
+
+fn required_strings<'a>(values: impl Iterator<Item = &'a str>) -> ParquetColumn {
+    ParquetColumn::Strings {
+        values: values.map(ByteArray::from).collect(),
+        def_levels: None,
+        rep_levels: None,
+    }
+}
+
+fn required_ints(values: impl Iterator<Item = i32>) -> ParquetColumn {
+    ParquetColumn::Ints {
+        values: values.collect(),
+        def_levels: None,
+    }
+}
+
+fn optional_strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ParquetColumn {
+    let mut column_values = Vec::new();
+    let mut def_levels = Vec::new();
+    for value in values {
+        def_levels.push(value.is_some() as i16);
+        column_values.extend(value.map(ByteArray::from));
+    }
+    ParquetColumn::Strings {
+        values: column_values,
+        def_levels: Some(def_levels),
+        rep_levels: None,
+    }
+}
+
+fn optional_ints(values: impl Iterator<Item = Option<i32>>) -> ParquetColumn {
+    let mut column_values = Vec::new();
+    let mut def_levels = Vec::new();
+    for value in values {
+        def_levels.push(value.is_some() as i16);
+        column_values.extend(value);
+    }
+    ParquetColumn::Ints {
+        values: column_values,
+        def_levels: Some(def_levels),
+    }
+}
+
+/// An empty list is recorded as a single level-0 entry with no value; every
+/// element after the first in a row repeats at level 1.
+fn string_lists<'a>(rows: impl Iterator<Item = Vec<&'a String>>) -> ParquetColumn {
+    let mut values = Vec::new();
+    let mut def_levels = Vec::new();
+    let mut rep_levels = Vec::new();
+    for row in rows {
+        if row.is_empty() {
+            def_levels.push(0);
+            rep_levels.push(0);
+        }
+        for (i, value) in row.into_iter().enumerate() {
+            def_levels.push(1);
+            rep_levels.push((i > 0) as i16);
+            values.push(ByteArray::from(value.as_str()));
+        }
+    }
+    ParquetColumn::Strings {
+        values,
+        def_levels: Some(def_levels),
+        rep_levels: Some(rep_levels),
+    }
+}
+
+/// Writes an Obsidian vault with one note per post, grouped into a folder per
+/// year with an `_index` note listing that year's posts. Links between
+/// archived posts become wiki-links so the vault's graph and backlinks work.
+pub fn export_obsidian(input: &Path, dir: &Path) -> Result<(), ScrapeError> {
+    let mut posts = helpers::read_posts_from_file(input)?;
+    helpers::sort_backup(&mut posts)?;
+
+    let notes: Vec<(&Post, Option<NaiveDate>, String)> = posts
+        .iter()
+        .zip(unique_slugs(&posts))
+        .map(|(post, name)| (post, helpers::post_date(post), name))
+        .collect();
+    let note_names: HashMap<String, String> = notes
+        .iter()
+        .map(|(post, _, name)| (helpers::canonicalize_post_url(&post.URL), name.clone()))
+        .collect();
+
+    let mut years: BTreeMap<String, Vec<(&Post, &str)>> = BTreeMap::new();
+    for (post, date, name) in &notes {
+        let folder = date
+            .map(|date| date.year().to_string())
+            .unwrap_or_else(|| "undated".to_string());
+        fs::create_dir_all(dir.join(&folder))?;
+
+        let mut out = front_matter(post, *date)?;
+        out.push_str(&wiki_links(post_markdown(post).trim(), &note_names));
+        out.push('\n');
+        fs::write(dir.join(&folder).join(format!("{}.md", name)), out)?;
+
+        years.entry(folder).or_default().push((post, name));
+    }
+
+    let mut vault_index = String::from("# Archive\n\n");
+    for (folder, year_posts) in &years {
+        let mut index = format!("# {}\n\n", folder);
+        for (post, name) in year_posts {
+            index.push_str(&format!(
+                "- [[{}|{}]]{}\n",
+                name,
+                convert::html_to_text(&post.title),
+                post.date
+                    .map(|date| format!(" ({})", date))
+                    .unwrap_or_default()
+            ));
+        }
+        fs::write(dir.join(folder).join("_index.md"), index)?;
+        vault_index.push_str(&format!(
+            "- [[{0}/_index|{0}]] ({1} posts)\n",
+            folder,
+            year_posts.len()
+        ));
+    }
+    fs::write(dir.join("_index.md"), vault_index)?;
+
+    println!(
+        "Obsidian vault with {} notes written to {}",
+        notes.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+/// Writes one plain-text (or, with `gemini`, gemtext) file per post and an
+/// index file listing them newest first.
+pub fn export_text(input: &Path, dir: &Path, gemini: bool) -> Result<(), ScrapeError> {
+    let mut posts = helpers::read_posts_from_file(input)?;
+    helpers::sort_backup(&mut posts)?;
+    fs::create_dir_all(dir)?;
+    let extension = if gemini { "gmi" } else { "txt" };
+
+    let mut index = if gemini {
+        String::from("# Archive\n\n")
+    } else {
+        String::from("ARCHIVE\n\n")
+    };
+    for (post, slug) in posts.iter().zip(unique_slugs(&posts)) {
+        let file_name = format!("{}.{}", slug, extension);
+        let title = convert::html_to_text(&post.title);
+        let date = post
+            .date
+            .map_or_else(|| "undated".to_string(), |date| date.to_string());
+
+        let blocks = helpers::post_blocks(post);
+        let out = if gemini {
+            let mut out = format!("# {}\n\n{}\n\n", title, date);
+            for block in blocks.iter() {
+                out.push_str(&gemtext_block(block));
+                out.push_str("\n\n");
+            }
+            for image in &post.images {
+                if !blocks
+                    .iter()
+                    .any(|block| matches!(block, Block::Image { src, .. } if src == image))
+                {
+                    out.push_str(&format!("=> {} Image\n", image));
+                }
+            }
+            out.push_str(&format!("=> {} Original post\n", post.URL));
+            index.push_str(&format!("=> {} {} {}\n", file_name, date, title));
+            out
+        } else {
+            index.push_str(&format!("{}  {}  {}\n", date, title, file_name));
+            let paragraphs: Vec<String> = blocks.iter().map(text_block).collect();
+            format!(
+                "{}\n{}\n{}\n\n{}\n",
+                title,
+                date,
+                post.URL,
+                paragraphs.join("\n\n")
+            )
+        };
+        fs::write(dir.join(file_name), out)?;
+    }
+    fs::write(dir.join(format!("index.{}", extension)), index)?;
+
+    println!(
+        "{} .{} files written to {}",
+        posts.len(),
+        extension,
+        dir.display()
+    );
+    Ok(())
+}
+
+/// File name stems for every post, numbered when two posts share a slug.
+pub fn unique_slugs<'a>(posts: impl IntoIterator<Item = &'a Post>) -> Vec<String> {
+    let mut taken: HashSet<String> = HashSet::new();
+    posts
+        .into_iter()
+        .map(|post| {
+            let slug = helpers::post_slug(post);
+            let mut name = slug.clone();
+            let mut suffix = 2;
+            while !taken.insert(name.clone()) {
+                name = format!("{}-{}", slug, suffix);
+                suffix += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+/// A Markdown link or image: `!`, the text and the destination.
+static MARKDOWN_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(!?)\[((?:[^\]\\]|\\.)*)\]\((<[^>]*>|[^)\s]+)\)").unwrap());
+
+/// Rewrites Markdown links that point at another archived post into
+/// `[[note|text]]` wiki-links. Image links and external links are kept.
+fn wiki_links(markdown: &str, note_names: &HashMap<String, String>) -> String {
+    MARKDOWN_LINK
+        .replace_all(markdown, |cap: &regex::Captures| {
+            let destination = cap[3].trim_start_matches('<').trim_end_matches('>');
+            let target = cap[1]
+                .is_empty()
+                .then(|| note_names.get(&helpers::canonicalize_post_url(destination)))
+                .flatten();
+            match target {
+                Some(name) if cap[2].is_empty() => format!("[[{}]]", name),
+                Some(name) => format!("[[{}|{}]]", name, &cap[2]),
+                None => cap[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// YAML front matter shared by the Markdown exports, followed by a blank line.
+fn front_matter(post: &Post, date: Option<NaiveDate>) -> Result<String, ScrapeError> {
+    let mut out = String::from("---\n");
+    out.push_str(&format!(
+        "title: {}\n",
+        yaml_string(&convert::html_to_text(&post.title))
+    ));
+    if let Some(date) = date {
+        out.push_str(&format!("date: {}\n", date.format("%Y-%m-%d")));
+    }
+    if !post.labels.is_empty() {
+        out.push_str(&format!("tags: {}\n", serde_json::to_string(&post.labels)?));
+    }
+    out.push_str(&format!("original_url: {}\n", yaml_string(&post.URL)));
+    if !post.images.is_empty() {
+        out.push_str(&format!(
+            "images: {}\n",
+            serde_json::to_string(&post.images)?
+        ));
+    }
+    out.push_str("---\n\n");
+    Ok(out)
+}
+
+fn text_block(block: &Block) -> String {
+    match block {
+        Block::List { ordered, items } => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                if *ordered {
+                    format!("{}. {}", index + 1, item)
+                } else {
+                    format!("* {}", item)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Image { src, caption, .. } => match caption {
+            Some(caption) => format!("[image: {} - {}]", src, caption),
+            None => format!("[image: {}]", src),
+        },
+        Block::Blockquote { text } => text
+            .lines()
+            .map(|line| format!("    {}", line).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => convert::block_text(block).unwrap_or_default(),
+    }
+}
+
+fn gemtext_block(block: &Block) -> String {
+    // Text lines starting with gemtext markers would change meaning.
+    let escape = |line: &str| {
+        if line.starts_with(['#', '>', '*', '`', '=']) {
+            format!(" {}", line)
+        } else {
+            line.to_string()
+        }
+    };
+    match block {
+        Block::Paragraph { text } => text.lines().map(escape).collect::<Vec<_>>().join("\n"),
+        Block::Heading { level, text } => {
+            format!("{} {}", "#".repeat((*level).clamp(2, 3) as usize), text)
+        }
+        Block::List { items, .. } => items
+            .iter()
+            .map(|item| format!("* {}", item.replace('\n', " ")))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Image { src, alt, caption } => format!(
+            "=> {} {}",
+            src,
+            caption.as_deref().or(alt.as_deref()).unwrap_or("Image")
+        ),
+        Block::Blockquote { text } => text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| format!("> {}", line))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Code { text } => format!("```\n{}\n```", text),
+    }
+}
+
+/// The post body as Markdown: the stored conversion when the backup has
+/// one, otherwise converted from the stored HTML or the content blocks.
+pub fn post_markdown(post: &Post) -> String {
+    if let Some(markdown) = &post.content_md {
+        return markdown.clone();
+    }
+    match &post.content_html {
+        Some(html) => convert::html_to_markdown(Html::parse_fragment(html).root_element()),
+        None => convert::blocks_to_markdown(&helpers::post_blocks(post)),
+    }
+}
+
+/// JSON strings are valid YAML scalars, which saves hand-written escaping.
+fn yaml_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+/// Points image references in a post body at local copies. Returns the
+/// rewritten markup and the local images the markup never referenced, which
+/// callers append after the body so no downloaded image is lost.
+pub fn localize_images<'a>(html: &str, images: &'a [(String, String)]) -> (String, Vec<&'a str>) {
+    let mut html = html.to_string();
+    let mut unplaced = Vec::new();
+    for (remote, local) in images {
+        let protocol_relative = remote
+            .trim_start_matches("http:")
+            .trim_start_matches("https:");
+        let before = html.clone();
+        html = html
+            .replace(&format!("\"{}\"", remote), &format!("\"{}\"", local))
+            .replace(
+                &format!("\"{}\"", protocol_relative),
+                &format!("\"{}\"", local),
+            );
+        if html == before {
+            unplaced.push(local.as_str());
+        }
+    }
+    (html, unplaced)
+}
+
+pub fn guess_image_mime(url: &str) -> String {
+    let lower = url.to_lowercase();
+    if lower.contains(".png") {
+        "image/png"
+    } else if lower.contains(".webp") {
+        "image/webp"
+    } else if lower.contains(".svg") {
+        "image/svg+xml"
+    } else {
+        "image/jpeg"
+    }
+    .to_string()
+}
+
+pub fn image_extension(mime: &str) -> &'static str {
+    match mime {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "jpg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_slugs_numbers_duplicates() {
+        let posts = [
+            Post::fixture("https://x.blogspot.com/2024/03/blog-post.html"),
+            Post::fixture("https://x.blogspot.com/2024/03/hello.html"),
+            Post::fixture("https://x.blogspot.com/2024/04/blog-post.html"),
+            Post::fixture("https://x.blogspot.com/2024/05/blog-post.html"),
+        ];
+        assert_eq!(
+            unique_slugs(&posts),
+            ["blog-post", "hello", "blog-post-2", "blog-post-3"]
+        );
+    }
+
+    #[test]
+    fn wiki_links_rewrite_links_to_archived_posts() {
+        let notes = HashMap::from([(
+            "https://x.blogspot.com/2024/03/a.html".to_string(),
+            "A post".to_string(),
+        )]);
+        let markdown = "See [the \\[first\\] one](https://x.blogspot.com/2024/03/a.html?m=1), \
+                        [Rust](https://www.rust-lang.org/) and \
+                        ![img](https://x.blogspot.com/2024/03/a.html)";
+        assert_eq!(
+            wiki_links(markdown, &notes),
+            "See [[A post|the \\[first\\] one]], [Rust](https://www.rust-lang.org/) and \
+             ![img](https://x.blogspot.com/2024/03/a.html)"
+        );
+    }
+
+    #[test]
+    fn unique_slugs_hashes_urls_without_a_path() {
+        let posts = [
+            Post::fixture("https://x.blogspot.com/"),
+            Post::fixture("https://y.blogspot.com/"),
+        ];
+        let slugs = unique_slugs(&posts);
+        assert_eq!(slugs[0].len(), 16);
+        assert!(slugs[0].chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(slugs[0], slugs[1]);
+    }
+}
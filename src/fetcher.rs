@@ -0,0 +1,682 @@
+use crate::cache::PageCache;
+use crate::concurrency::{AdaptiveLimit, Permit};
+use crate::error::ScrapeError;
+use crate::warc::WarcWriter;
+use crate::HttpArgs;
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, RETRY_AFTER, USER_AGENT,
+};
+use reqwest::StatusCode;
+use reqwest::{Method, Proxy, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// Cache validators returned with a page, replayed on later requests so an
+/// unchanged page can be answered with 304 Not Modified.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+/// Wraps the shared HTTP client together with state that every worker thread
+/// must observe, such as a pause requested by the server through `Retry-After`.
+pub struct Fetcher {
+    client: Client,
+    paused_until: Mutex<Option<Instant>>,
+    rate_limited: AtomicUsize,
+    bytes_downloaded: AtomicU64,
+    min_interval: Duration,
+    next_request_at: Mutex<Instant>,
+    validators: Mutex<HashMap<String, Validators>>,
+    cache: Option<PageCache>,
+    snapshots: Option<PageCache>,
+    cache_hits: AtomicUsize,
+    redirects: Mutex<HashMap<String, String>>,
+    warc: Option<WarcWriter>,
+    /// Headers the client adds to every request, kept for WARC request records.
+    default_headers: HeaderMap,
+    /// Headers from --header, only sent to the hosts in `header_hosts` since
+    /// they often carry credentials for a private blog.
+    user_headers: HeaderMap,
+    header_hosts: Mutex<HashSet<String>>,
+    /// Limits requests in flight across all threads, when --max-requests is
+    /// set or `limit_requests` was called.
+    request_limit: Option<AdaptiveLimit>,
+}
+
+impl Fetcher {
+    pub fn new(http: &HttpArgs) -> Result<Self, ScrapeError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &http.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ScrapeError::Config(e.to_string()))?;
+            let value =
+                HeaderValue::from_str(value).map_err(|e| ScrapeError::Config(e.to_string()))?;
+            headers.append(name, value);
+        }
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(&http.user_agent)
+                .map_err(|e| ScrapeError::Config(e.to_string()))?,
+        );
+
+        let mut builder = Client::builder()
+            .user_agent(&http.user_agent)
+            .connect_timeout(Duration::from_secs(http.connect_timeout))
+            .timeout(Duration::from_secs(http.timeout))
+            .pool_max_idle_per_host(http.pool_size);
+        if let Some(proxy) = &http.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        let client = builder.build()?;
+        let cache = http.cache_dir.clone().map(PageCache::new).transpose()?;
+        let warc = http.warc.as_deref().map(WarcWriter::create).transpose()?;
+
+        Ok(Fetcher {
+            client,
+            paused_until: Mutex::new(None),
+            rate_limited: AtomicUsize::new(0),
+            bytes_downloaded: AtomicU64::new(0),
+            min_interval: Duration::from_millis(http.delay_ms),
+            next_request_at: Mutex::new(Instant::now()),
+            validators: Mutex::new(HashMap::new()),
+            cache,
+            snapshots: None,
+            cache_hits: AtomicUsize::new(0),
+            redirects: Mutex::new(HashMap::new()),
+            warc,
+            default_headers,
+            user_headers: headers,
+            header_hosts: Mutex::new(HashSet::new()),
+            request_limit: http
+                .max_requests
+                .map(|max| AdaptiveLimit::new(max.get(), false)),
+        })
+    }
+
+    pub fn fetch_html(&self, url: &str) -> Result<String, ScrapeError> {
+        let _slot = self.request_slot();
+        let response = self.get(url, HeaderMap::new())?;
+        self.read_html(url, &HeaderMap::new(), response, false)
+    }
+
+    /// Like `fetch_html`, but answers from the page cache when one is
+    /// configured. Only used for post pages, since listing pages change
+    /// whenever something is published. A cached copy is only used while its
+    /// validators match the latest ones seen for the URL.
+    pub fn fetch_html_cached(&self, url: &str) -> Result<String, ScrapeError> {
+        let Some(cache) = &self.cache else {
+            return self.fetch_html(url);
+        };
+        let expected = self.validators.lock().unwrap().get(url).cloned();
+        if let Some(html) = cache.get(url, expected.as_ref())? {
+            debug!(url, "cache hit");
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(html);
+        }
+        let _slot = self.request_slot();
+        let response = self.get(url, HeaderMap::new())?;
+        self.read_html(url, &HeaderMap::new(), response, true)
+    }
+
+    /// Fetches a page using the validators stored from an earlier response.
+    /// Returns `None` when the server reports the page has not changed.
+    pub fn fetch_html_if_modified(&self, url: &str) -> Result<Option<String>, ScrapeError> {
+        let mut headers = HeaderMap::new();
+        if let Some(validators) = self.validators.lock().unwrap().get(url) {
+            if let Some(etag) = validators.etag.as_deref() {
+                if let Ok(value) = HeaderValue::from_str(etag) {
+                    headers.insert(IF_NONE_MATCH, value);
+                }
+            }
+            if let Some(last_modified) = validators.last_modified.as_deref() {
+                if let Ok(value) = HeaderValue::from_str(last_modified) {
+                    headers.insert(IF_MODIFIED_SINCE, value);
+                }
+            }
+        }
+
+        let _slot = self.request_slot();
+        let response = self.get(url, headers.clone())?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            debug!(url, "not modified");
+            return Ok(None);
+        }
+        self.read_html(url, &headers, response, true).map(Some)
+    }
+
+    /// Reads a successful response body. Post pages are also written to the
+    /// page cache when `cache_page` is set; listing, sitemap and API pages
+    /// are never read back from it, so they are not stored.
+    fn read_html(
+        &self,
+        url: &str,
+        request_headers: &HeaderMap,
+        response: Response,
+        cache_page: bool,
+    ) -> Result<String, ScrapeError> {
+        if response.url().as_str() != url {
+            debug!(url, final_url = %response.url(), "followed redirect");
+            self.redirects
+                .lock()
+                .unwrap()
+                .insert(url.to_string(), response.url().to_string());
+        }
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(String::from)
+        };
+        let validators = Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+            return Err(ScrapeError::Gone {
+                url: url.to_string(),
+                status,
+            });
+        }
+        let error = status_error(url, &response);
+        let response_headers = response.headers().clone();
+        let bytes = response.bytes()?;
+        self.bytes_downloaded
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        self.record(url, request_headers, status, &response_headers, &bytes);
+        // Error pages must not reach the cache or the validators, or later
+        // runs would keep serving them
+        if let Some(error) = error {
+            return Err(error);
+        }
+        let body = String::from_utf8_lossy(&bytes).into_owned();
+
+        if let Some(cache) = self.cache.as_ref().filter(|_| cache_page) {
+            if let Err(e) = cache.put(url, &body, &validators) {
+                warn!(url, error = %e, "failed to write page to cache");
+            }
+        }
+        if validators.etag.is_some() || validators.last_modified.is_some() {
+            self.validators
+                .lock()
+                .unwrap()
+                .insert(url.to_string(), validators);
+        }
+
+        Ok(body)
+    }
+
+    /// Loads validators saved by an earlier run, replacing any held for
+    /// another archive. A missing file is not an error.
+    pub fn load_validators(&self, path: &Path) -> Result<(), ScrapeError> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.validators.lock().unwrap().clear();
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let stored: HashMap<String, Validators> = serde_json::from_str(&json)?;
+        debug!(count = stored.len(), "loaded cache validators");
+        *self.validators.lock().unwrap() = stored;
+        Ok(())
+    }
+
+    pub fn save_validators(&self, path: &Path) -> Result<(), ScrapeError> {
+        let validators = self.validators.lock().unwrap();
+        let sorted: BTreeMap<&String, &Validators> = validators.iter().collect();
+        fs::write(path, serde_json::to_string_pretty(&sorted)?)?;
+        Ok(())
+    }
+
+    pub fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>), ScrapeError> {
+        let _slot = self.request_slot();
+        let response = self.get(url, HeaderMap::new())?;
+        if let Some(error) = status_error(url, &response) {
+            return Err(error);
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split(';').next().unwrap_or(value).trim().to_string());
+        let status = response.status();
+        let response_headers = response.headers().clone();
+        let bytes = response.bytes()?.to_vec();
+        self.bytes_downloaded
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        self.record(url, &HeaderMap::new(), status, &response_headers, &bytes);
+        Ok((bytes, content_type))
+    }
+
+    /// Sends a JSON body to a third-party endpoint such as a webhook. These
+    /// requests skip the throttling and WARC recording used for the blog.
+    pub fn post_json<T: Serialize>(&self, url: &str, body: &T) -> Result<(), ScrapeError> {
+        debug!(url, "POST");
+        self.client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(body)?)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Writes the exchange to the WARC file, if one was requested.
+    fn record(
+        &self,
+        url: &str,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        response_headers: &HeaderMap,
+        body: &[u8],
+    ) {
+        let Some(warc) = &self.warc else {
+            return;
+        };
+        let mut sent = self.default_headers.clone();
+        sent.extend(self.headers_for(url, request_headers));
+        if let Err(e) = warc.record_exchange(url, &sent, status, response_headers, body) {
+            warn!(url, error = %e, "failed to write WARC record");
+        }
+    }
+
+    /// Sends the --header values with requests to the host of `url`, the blog
+    /// being scraped.
+    pub fn send_headers_to(&self, url: &str) {
+        if let Some(host) = host(url) {
+            self.header_hosts.lock().unwrap().insert(host);
+        }
+    }
+
+    /// The headers for a request to `url`: the --header values when it goes
+    /// to the blog, followed by the request's own.
+    fn headers_for(&self, url: &str, headers: &HeaderMap) -> HeaderMap {
+        let to_blog =
+            host(url).is_some_and(|host| self.header_hosts.lock().unwrap().contains(&host));
+        if !to_blog {
+            return headers.clone();
+        }
+        let mut all = self.user_headers.clone();
+        all.extend(headers.clone());
+        all
+    }
+
+    /// Number of responses that asked the scraper to slow down during this run.
+    pub fn rate_limited_count(&self) -> usize {
+        self.rate_limited.load(Ordering::Relaxed)
+    }
+
+    /// Keeps a copy of every post page passed to `save_snapshot` in `dir`.
+    pub fn save_snapshots_to(&mut self, dir: PathBuf) -> Result<(), ScrapeError> {
+        self.snapshots = Some(PageCache::new(dir)?);
+        Ok(())
+    }
+
+    pub fn save_snapshot(&self, url: &str, html: &str) {
+        if let Some(snapshots) = &self.snapshots {
+            if let Err(e) = snapshots.put(url, html, &Validators::default()) {
+                warn!(url, error = %e, "failed to save snapshot");
+            }
+        }
+    }
+
+    /// The URL a request for `url` ended up at, if it was redirected.
+    pub fn final_url(&self, url: &str) -> Option<String> {
+        self.redirects.lock().unwrap().get(url).cloned()
+    }
+
+    /// Number of pages answered from the on-disk cache during this run.
+    pub fn cache_hits(&self) -> usize {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Total size of response bodies downloaded during this run.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bytes_downloaded.load(Ordering::Relaxed)
+    }
+
+    /// Status a URL answers with, following redirects, without downloading
+    /// the body. Servers that refuse HEAD requests are asked again with GET.
+    /// Meant for third-party links, so 429 and 503 are returned like any other
+    /// status rather than pausing every thread the way they do for the blog.
+    pub fn check_status(&self, url: &str) -> Result<StatusCode, ScrapeError> {
+        let _slot = self.request_slot();
+        let status = self
+            .send_once(&Method::HEAD, url, &HeaderMap::new())?
+            .status();
+        if status == StatusCode::METHOD_NOT_ALLOWED || status == StatusCode::NOT_IMPLEMENTED {
+            return Ok(self
+                .send_once(&Method::GET, url, &HeaderMap::new())?
+                .status());
+        }
+        Ok(status)
+    }
+
+    /// Allows at most `max` requests in flight at once, lowering the limit
+    /// while requests are failing when `adaptive` is set.
+    pub fn limit_requests(&mut self, max: usize, adaptive: bool) {
+        self.request_limit = Some(AdaptiveLimit::new(max, adaptive));
+    }
+
+    /// Reports whether an attempt at a page failed in a way that suggests
+    /// the server is struggling, so an adaptive limit can react.
+    pub fn record_attempt(&self, failed: bool) {
+        if let Some(limit) = &self.request_limit {
+            limit.record(failed);
+        }
+    }
+
+    /// Fewest requests allowed at once so far, if requests are limited.
+    pub fn lowest_request_limit(&self) -> Option<usize> {
+        self.request_limit.as_ref().map(AdaptiveLimit::lowest)
+    }
+
+    /// Waits for a free request slot, held until the response body has been
+    /// read so slow downloads count against the limit too.
+    fn request_slot(&self) -> Option<Permit<'_>> {
+        self.request_limit.as_ref().map(AdaptiveLimit::acquire)
+    }
+
+    fn get(&self, url: &str, headers: HeaderMap) -> Result<Response, ScrapeError> {
+        self.send(Method::GET, url, headers)
+    }
+
+    fn send(&self, method: Method, url: &str, headers: HeaderMap) -> Result<Response, ScrapeError> {
+        let mut attempts = 0;
+
+        loop {
+            self.wait_while_paused();
+            let response = self.send_once(&method, url, &headers)?;
+            let status = response.status();
+
+            if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE
+            {
+                return Ok(response);
+            }
+
+            attempts += 1;
+            self.rate_limited.fetch_add(1, Ordering::Relaxed);
+            let retry_after = parse_retry_after(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER);
+            if attempts > MAX_RATE_LIMIT_RETRIES {
+                return Err(ScrapeError::RateLimited {
+                    status,
+                    retry_after,
+                });
+            }
+            warn!(
+                url,
+                status = %status,
+                retry_after_secs = retry_after.as_secs(),
+                "rate limited, pausing all requests"
+            );
+            self.pause_for(retry_after);
+        }
+    }
+
+    fn send_once(
+        &self,
+        method: &Method,
+        url: &str,
+        headers: &HeaderMap,
+    ) -> Result<Response, ScrapeError> {
+        self.wait_for_turn();
+        debug!(url, "{}", method);
+        Ok(self
+            .client
+            .request(method.clone(), url)
+            .headers(self.headers_for(url, headers))
+            .send()?)
+    }
+
+    /// Pauses every thread sharing this fetcher, extending any pause already in effect.
+    fn pause_for(&self, duration: Duration) {
+        let resume_at = Instant::now() + duration;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        if paused_until.is_none_or(|current| current < resume_at) {
+            *paused_until = Some(resume_at);
+        }
+    }
+
+    /// Reserves the next request slot so that requests from all threads are
+    /// spaced at least `min_interval` apart, then sleeps until that slot.
+    fn wait_for_turn(&self) {
+        if self.min_interval.is_zero() {
+            return;
+        }
+
+        let slot = {
+            let mut next_request_at = self.next_request_at.lock().unwrap();
+            let slot = (*next_request_at).max(Instant::now());
+            *next_request_at = slot + self.min_interval;
+            slot
+        };
+
+        let wait = slot.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    fn wait_while_paused(&self) {
+        loop {
+            let resume_at = *self.paused_until.lock().unwrap();
+            let remaining = resume_at.map_or(Duration::ZERO, |resume_at| {
+                resume_at.saturating_duration_since(Instant::now())
+            });
+            if remaining.is_zero() {
+                return;
+            }
+            thread::sleep(remaining);
+        }
+    }
+}
+
+/// The error an unsuccessful response stands for. Rate limiting and server
+/// errors are retryable, other statuses are not.
+fn status_error(url: &str, response: &Response) -> Option<ScrapeError> {
+    let status = response.status();
+    if status.is_success() {
+        return None;
+    }
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Some(ScrapeError::RateLimited {
+            status,
+            retry_after: parse_retry_after(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER),
+        });
+    }
+    Some(ScrapeError::Http {
+        url: url.to_string(),
+        status,
+    })
+}
+
+fn host(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(str::to_lowercase)
+}
+
+/// Parses a `Name: value` command line argument into a header pair.
+pub fn parse_header(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("expected \"Name: value\", got {:?}", raw))?;
+    let name = name.trim();
+    let value = value.trim();
+
+    HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+    HeaderValue::from_str(value).map_err(|e| e.to_string())?;
+
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// `Retry-After` may be either a number of seconds or an HTTP date.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_at = DateTime::parse_from_rfc2822(value).ok()?;
+    (retry_at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    fn http_args() -> HttpArgs {
+        HttpArgs {
+            user_agent: "scrape_blogger-test".to_string(),
+            headers: Vec::new(),
+            connect_timeout: 5,
+            timeout: 5,
+            pool_size: 1,
+            proxy: None,
+            max_requests: None,
+            delay_ms: 0,
+            cache_dir: None,
+            warc: None,
+        }
+    }
+
+    /// Answers one request per entry in `responses`, in order, and returns
+    /// the server's base URL.
+    fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn link_checks_do_not_pause_for_retry_after() {
+        let unavailable = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 30\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let base = serve(vec![unavailable]);
+        let fetcher = Fetcher::new(&http_args()).unwrap();
+
+        let started = Instant::now();
+        let status = fetcher.check_status(&format!("{}/", base)).unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(fetcher.rate_limited_count(), 0);
+        assert!(fetcher.paused_until.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn sends_user_headers_only_to_the_blog() {
+        let mut args = http_args();
+        args.headers = vec![("Cookie".to_string(), "session=secret".to_string())];
+        let fetcher = Fetcher::new(&args).unwrap();
+        fetcher.send_headers_to("https://Example.blogspot.com/");
+
+        let mut request = HeaderMap::new();
+        request.insert(IF_NONE_MATCH, HeaderValue::from_static("\"abc\""));
+        let to_blog = fetcher.headers_for("https://example.blogspot.com/2024/03/a.html", &request);
+        assert_eq!(to_blog.get("cookie").unwrap(), "session=secret");
+        assert_eq!(to_blog.get(IF_NONE_MATCH).unwrap(), "\"abc\"");
+
+        for elsewhere in [
+            "https://hooks.example.com/notify",
+            "https://web.archive.org/web/2024/https://example.blogspot.com/",
+            "https://blogger.googleusercontent.com/img/a.jpg",
+        ] {
+            let headers = fetcher.headers_for(elsewhere, &request);
+            assert!(headers.get("cookie").is_none(), "{}", elsewhere);
+            assert!(headers.get(IF_NONE_MATCH).is_some());
+        }
+    }
+
+    fn retry_after(value: &str) -> Option<Duration> {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        parse_retry_after(&headers)
+    }
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        assert_eq!(retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(" 3 "), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn parses_retry_after_dates() {
+        let at = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let wait = retry_after(&at).unwrap();
+        assert!(wait > Duration::from_secs(50) && wait <= Duration::from_secs(60));
+        // A date already past leaves nothing to wait for
+        assert_eq!(retry_after("Sun, 03 Mar 2024 10:00:00 +0000"), None);
+        assert_eq!(retry_after("soon"), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn waits_out_retry_after_before_retrying() {
+        let base = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let fetcher = Fetcher::new(&http_args()).unwrap();
+
+        let started = Instant::now();
+        let body = fetcher
+            .fetch_html(&format!("{}/2024/03/a.html", base))
+            .unwrap();
+        assert_eq!(body, "ok");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(fetcher.rate_limited_count(), 1);
+    }
+
+    #[test]
+    fn gives_up_after_repeated_rate_limiting() {
+        let limited =
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let base = serve(vec![limited; MAX_RATE_LIMIT_RETRIES as usize + 1]);
+        let fetcher = Fetcher::new(&http_args()).unwrap();
+
+        let err = fetcher.fetch_html(&format!("{}/", base)).unwrap_err();
+        assert!(matches!(
+            err,
+            ScrapeError::RateLimited {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                ..
+            }
+        ));
+        assert_eq!(
+            fetcher.rate_limited_count(),
+            MAX_RATE_LIMIT_RETRIES as usize + 1
+        );
+    }
+}
@@ -1,38 +1,78 @@
-use crate::Post;
-use chrono::NaiveDate;
+use crate::attachments;
+use crate::convert::{self, html_to_text};
+use crate::dates;
+use crate::error::ScrapeError;
+use crate::links;
+use crate::profile::SelectorProfile;
+use crate::{BackupFormat, DateLocale, LogArgs, LogFormat};
+use crate::{Block, Post};
+use chrono::{Datelike, NaiveDate};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use fs2::FileExt;
 use regex::Regex;
-use reqwest::blocking::get;
+use reqwest::Url;
 use scraper::{Html, Selector};
-use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-
-pub fn create_log_file() -> Result<Arc<Mutex<File>>, Box<dyn std::error::Error>> {
-    let log_file = Arc::new(Mutex::new(
-        fs::File::create("scrape_blogger.txt").unwrap_or_else(|e| {
-            eprintln!("Failed to create log file: {}", e);
-            std::process::exit(1);
-        }),
-    ));
-    println!(
-        "scrape_blogger.txt log file created successfully at {}",
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+pub fn init_logging(log: &LogArgs) -> Result<(), ScrapeError> {
+    let log_file = File::create(&log.log_file).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to create log file {}: {}", log.log_file.display(), e),
+        )
+    })?;
+    let filter =
+        EnvFilter::try_new(&log.log_level).map_err(|e| ScrapeError::Config(e.to_string()))?;
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(Mutex::new(log_file))
+        .with_ansi(false);
+    match log.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    // Kept off stdout so commands like schema can be piped
+    eprintln!(
+        "{} log file created successfully at {}",
+        log.log_file.display(),
         env::current_dir()?.display()
     );
 
-    Ok(log_file)
+    Ok(())
 }
 
-pub fn fetch_html(url: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let response = get(url)?.text()?;
-    Ok(response)
+/// Installs a Ctrl+C handler that asks the scrape to stop so that posts
+/// collected so far can still be written. A second Ctrl+C exits immediately.
+pub fn install_interrupt_handler() -> Result<Arc<AtomicBool>, ScrapeError> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = interrupted.clone();
+    ctrlc::set_handler(move || {
+        if handler_flag.swap(true, Ordering::SeqCst) {
+            eprintln!("\nInterrupted again, exiting without saving");
+            std::process::exit(130);
+        }
+        eprintln!("\nInterrupt received, finishing in-flight posts and saving progress...");
+    })?;
+
+    Ok(interrupted)
 }
 
-pub fn find_older_posts_link(document: &Html) -> Option<String> {
-    let older_link_selector = Selector::parse("a.blog-pager-older-link").unwrap();
+pub fn find_older_posts_link(document: &Html, selectors: &SelectorProfile) -> Option<String> {
+    let older_link_selector = Selector::parse(&selectors.older_posts_link).ok()?;
 
     document
         .select(&older_link_selector)
@@ -41,64 +81,374 @@ pub fn find_older_posts_link(document: &Html) -> Option<String> {
         .next()
 }
 
-pub fn extract_id_from_title(title: &str) -> Option<String> {
-    let re = Regex::new(r"\((\d+)\)$").unwrap();
-    re.captures(title)
-        .and_then(|cap| cap.get(1).map(|m| m.as_str().to_string()))
+/// Validates a blog home page URL and gives it a trailing slash so paths can
+/// be appended to it.
+pub fn normalize_base_url(raw: &str) -> Result<String, ScrapeError> {
+    let mut url = Url::parse(raw).map_err(|e| ScrapeError::Config(format!("{}: {}", raw, e)))?;
+    if url.host_str().is_none() {
+        return Err(ScrapeError::Config(format!("{} has no host", raw)));
+    }
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url.to_string())
 }
 
-pub fn sort_backup(backup: &mut Vec<Post>) -> Result<(), Box<dyn std::error::Error>> {
-    let re = Regex::new(r"(\d{1,2} \w+ \d{4})").unwrap();
+/// Matches post permalinks (`/YYYY/MM/slug.html`) on the same host as the
+/// blog, which may be a custom domain rather than `*.blogspot.com`.
+pub fn post_link_regex(base_url: &str) -> Result<Regex, ScrapeError> {
+    let url = Url::parse(base_url).map_err(|e| ScrapeError::Config(e.to_string()))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| ScrapeError::Config(format!("{} has no host", base_url)))?;
+    Regex::new(&format!(
+        r"^https?://{}/\d+/.*\.html$",
+        regex::escape(host)
+    ))
+    .map_err(|e| ScrapeError::Config(e.to_string()))
+}
 
-    backup.sort_by(|a, b| {
-        let a_date = a.date.as_ref().and_then(|d| {
-            re.captures(d)
-                .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%d %B %Y").ok())
-        });
+/// Country-specific Blogger hosts such as `example.blogspot.de`.
+static COUNTRY_HOST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.+)\.blogspot\.[a-z]{2,3}(\.[a-z]{2})?$").unwrap());
 
-        let b_date = b.date.as_ref().and_then(|d| {
-            re.captures(d)
-                .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%d %B %Y").ok())
-        });
+/// The `/YYYY/MM/` segment of a post URL.
+static URL_YEAR_MONTH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"/(\d{4})/(\d{2})/").unwrap());
+
+/// Reduces the variants Blogger links to (`?m=1` mobile pages, fragments and
+/// country redirects such as `.blogspot.de`) to a single form for each post.
+pub fn canonicalize_post_url(raw: &str) -> String {
+    let Ok(mut url) = Url::parse(raw) else {
+        return raw.to_string();
+    };
+    url.set_query(None);
+    url.set_fragment(None);
 
-        b_date.cmp(&a_date) //desc
+    let canonical_host = url.host_str().and_then(|host| {
+        COUNTRY_HOST
+            .captures(host)
+            .map(|cap| format!("{}.blogspot.com", &cap[1]))
     });
+    if let Some(host) = canonical_host {
+        let _ = url.set_host(Some(&host));
+        let _ = url.set_scheme("https");
+    }
 
-    Ok(())
+    url.to_string()
 }
 
-pub fn sort_backup_asc(backup: &mut Vec<&Post>) -> Result<(), Box<dyn std::error::Error>> {
-    let re = Regex::new(r"(\d{1,2} \w+ \d{4})").unwrap();
+/// Builds the `search/label/<name>` listing URL for a label, which is paged
+/// with the same "Older Posts" links as the main feed.
+pub fn label_url(base_url: &str, label: &str) -> Result<String, ScrapeError> {
+    let mut url = Url::parse(base_url).map_err(|e| ScrapeError::Config(e.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|_| ScrapeError::Config(format!("{} cannot be a base URL", base_url)))?
+        .pop_if_empty()
+        .extend(["search", "label", label]);
+    Ok(url.to_string())
+}
 
-    backup.sort_by(|a, b| {
-        let a_date = a.date.as_ref().and_then(|d| {
-            re.captures(d)
-                .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%d %B %Y").ok())
-        });
+/// Reads a post's number from its title using a profile's `id_pattern`.
+pub fn extract_id_from_title(title: &str, pattern: &Regex) -> Option<String> {
+    pattern
+        .captures(title)
+        .and_then(|cap| cap.get(1).or_else(|| cap.get(0)))
+        .map(|m| m.as_str().to_string())
+}
 
-        let b_date = b.date.as_ref().and_then(|d| {
-            re.captures(d)
-                .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%d %B %Y").ok())
-        });
+/// Extracts the year and month from the `/YYYY/MM/` segment of a post URL.
+pub fn url_year_month(url: &str) -> Option<(i32, u32)> {
+    let cap = URL_YEAR_MONTH.captures(url)?;
+    Some((cap[1].parse().ok()?, cap[2].parse().ok()?))
+}
+
+/// The structure of a post body, or for posts archived without it, one
+/// paragraph per line of the plain text.
+pub fn post_blocks(post: &Post) -> Cow<'_, [Block]> {
+    if !post.blocks.is_empty() {
+        return Cow::Borrowed(&post.blocks);
+    }
+    Cow::Owned(
+        post.content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| Block::Paragraph {
+                text: line.to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// Publication date of a post, from its date header or, failing that, the
+/// first day of the month in its URL.
+pub fn post_date(post: &Post) -> Option<NaiveDate> {
+    post.date.or_else(|| {
+        let (year, month) = url_year_month(&post.URL)?;
+        NaiveDate::from_ymd_opt(year, month, 1)
+    })
+}
+
+/// Name shown for an archive: the host name of its posts, such as
+/// `example.blogspot.com`, since backups don't record the blog's title.
+pub fn blog_name(posts: &[Post]) -> String {
+    posts
+        .first()
+        .and_then(|post| Url::parse(&post.URL).ok())
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "Blog archive".to_string())
+}
+
+/// File name stem for a post: the last segment of its Blogger URL without
+/// the `.html` extension, or a hash of the URL when there is none.
+pub fn post_slug(post: &Post) -> String {
+    Url::parse(&post.URL)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()?
+                .next_back()
+                .map(|segment| segment.trim_end_matches(".html").to_string())
+        })
+        .filter(|slug| !slug.is_empty() && slug != "..")
+        .unwrap_or_else(|| sha256_hex(post.URL.as_bytes())[..16].to_string())
+}
+
+/// Hex SHA-256 of the title and text of a post, used to detect edits.
+pub fn content_hash(post: &Post) -> String {
+    sha256_hex(format!("{}\0{}", post.title, post.content).as_bytes())
+}
+
+/// Excerpt length used unless --excerpt-words says otherwise.
+pub const DEFAULT_EXCERPT_WORDS: usize = 50;
 
-        a_date.cmp(&b_date) //asc
+/// Average adult reading speed used for `reading_minutes`.
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Fills in the word count, reading time and excerpt of a post from its
+/// plain text. Excerpts cut short end with an ellipsis.
+pub fn fill_reading_stats(post: &mut Post, excerpt_words: usize) {
+    let words: Vec<&str> = post.content.split_whitespace().collect();
+    post.word_count = words.len();
+    post.reading_minutes = words.len().div_ceil(WORDS_PER_MINUTE) as u32;
+    post.excerpt = (!words.is_empty() && excerpt_words > 0).then(|| {
+        let mut excerpt = words[..words.len().min(excerpt_words)].join(" ");
+        if words.len() > excerpt_words {
+            excerpt.push('…');
+        }
+        excerpt
     });
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Newest first. Posts without a date sort by the month in their URL.
+pub fn sort_backup(backup: &mut [Post]) -> Result<(), ScrapeError> {
+    backup.sort_by_cached_key(|post| (Reverse(post_date(post)), post.URL.clone())); //desc, ties by url
+
+    Ok(())
+}
+
+pub fn sort_backup_asc(backup: &mut Vec<&Post>) -> Result<(), ScrapeError> {
+    backup.sort_by_cached_key(|post| (post_date(post), post.URL.clone())); //asc, ties by url
 
     Ok(())
 }
 
-pub fn write_to_file(data: &[Post], file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let path = Path::new(file_path);
+pub fn write_to_file(
+    data: &[Post],
+    file_path: &str,
+    format: BackupFormat,
+) -> Result<(), ScrapeError> {
+    write_posts(data, Path::new(file_path), format)?;
+    println!("Data written to {}", file_path);
+    Ok(())
+}
+
+/// Shifts `file.1` to `file.2` and so on, dropping anything older than
+/// `depth`, then moves the current file to `file.1`.
+pub fn rotate_backups(file_path: &str, depth: usize) -> Result<(), ScrapeError> {
+    if depth == 0 || !Path::new(file_path).exists() {
+        return Ok(());
+    }
+
+    let oldest = format!("{}.{}", file_path, depth);
+    if Path::new(&oldest).exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for generation in (1..depth).rev() {
+        let from = format!("{}.{}", file_path, generation);
+        if Path::new(&from).exists() {
+            fs::rename(&from, format!("{}.{}", file_path, generation + 1))?;
+        }
+    }
+    fs::rename(file_path, format!("{}.1", file_path))?;
+    println!("Previous {} kept as {}.1", file_path, file_path);
+
+    Ok(())
+}
+
+/// Self-describe tag that marks a file as CBOR (RFC 8949, section 3.4.6),
+/// which keeps it distinguishable from MessagePack when reading.
+const CBOR_MAGIC: [u8; 3] = [0xd9, 0xd9, 0xf7];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const ZSTD_LEVEL: i32 = 19;
+
+/// Streams posts to `path` one at a time, compressing them when the name
+/// ends in `.gz` or `.zst`, so memory use does not grow with the archive.
+pub fn write_posts(data: &[Post], path: &Path, format: BackupFormat) -> Result<(), ScrapeError> {
     let file = File::create(path)?;
     file.lock_exclusive()?;
-    let json_data = serde_json::to_string_pretty(data)?;
-    fs::write(path, json_data)?;
+    let mut writer = BackupWriter::new(&file, path)?;
+    let binary_error = |e: &dyn std::fmt::Display| ScrapeError::Binary(e.to_string());
+    match format {
+        BackupFormat::Json => serde_json::to_writer_pretty(&mut writer, data)?,
+        BackupFormat::Jsonl => {
+            for post in data {
+                serde_json::to_writer(&mut writer, post)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        // Field names are kept so optional fields can be skipped and added
+        // over time, as with JSON.
+        BackupFormat::Msgpack => {
+            rmp_serde::encode::write_named(&mut writer, data).map_err(|e| binary_error(&e))?
+        }
+        BackupFormat::Cbor => {
+            writer.write_all(&CBOR_MAGIC)?;
+            ciborium::into_writer(data, &mut writer).map_err(|e| binary_error(&e))?;
+        }
+    }
+    writer.finish()?;
     file.unlock()?;
-    println!("Data written to {}", file_path);
     Ok(())
 }
 
-pub fn find_duplicates(backup: &[Post], logfile: Arc<Mutex<File>>) {
+/// Output stream for a backup file, compressed according to its name.
+enum BackupWriter<'a> {
+    Plain(BufWriter<&'a File>),
+    Gzip(GzEncoder<BufWriter<&'a File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<&'a File>>),
+}
+
+impl<'a> BackupWriter<'a> {
+    fn new(file: &'a File, path: &Path) -> Result<Self, ScrapeError> {
+        let buffered = BufWriter::new(file);
+        let writer = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => BackupWriter::Gzip(GzEncoder::new(buffered, Compression::default())),
+            Some("zst") => BackupWriter::Zstd(zstd::Encoder::new(buffered, ZSTD_LEVEL)?),
+            _ => BackupWriter::Plain(buffered),
+        };
+        Ok(writer)
+    }
+
+    /// Writes any compression trailer and flushes everything to the file.
+    fn finish(self) -> std::io::Result<()> {
+        let mut buffered = match self {
+            BackupWriter::Plain(buffered) => buffered,
+            BackupWriter::Gzip(encoder) => encoder.finish()?,
+            BackupWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        buffered.flush()
+    }
+}
+
+impl Write for BackupWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            BackupWriter::Plain(writer) => writer.write(buf),
+            BackupWriter::Gzip(writer) => writer.write(buf),
+            BackupWriter::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            BackupWriter::Plain(writer) => writer.flush(),
+            BackupWriter::Gzip(writer) => writer.flush(),
+            BackupWriter::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+/// The URL identifying a post in the archive: its canonical URL when the
+/// page declared one, otherwise the URL it was scraped from, canonicalized.
+pub fn canonical_key(post: &Post) -> String {
+    canonicalize_post_url(post.canonical_url.as_deref().unwrap_or(&post.URL))
+}
+
+/// Maps both the scraped and the canonical URL of every post, canonicalized,
+/// to the post's position in `posts`.
+pub fn archive_index(posts: &[Post]) -> HashMap<String, usize> {
+    let mut index = HashMap::new();
+    for (position, post) in posts.iter().enumerate() {
+        index.insert(canonicalize_post_url(&post.URL), position);
+        index.insert(canonical_key(post), position);
+    }
+    index
+}
+
+/// Collapses posts that share a canonical URL, such as a renamed post that
+/// was archived under both its old and new address. The most recently
+/// checked copy is kept.
+pub fn dedupe_canonical(posts: Vec<Post>) -> Vec<Post> {
+    let mut kept: Vec<Post> = Vec::with_capacity(posts.len());
+    let mut index_by_key: HashMap<String, usize> = HashMap::new();
+    for post in posts {
+        let key = canonical_key(&post);
+        match index_by_key.get(&key) {
+            Some(&index) => {
+                let existing = &mut kept[index];
+                let dropped = if post.last_checked > existing.last_checked {
+                    std::mem::replace(existing, post)
+                } else {
+                    post
+                };
+                warn!(
+                    canonical_url = %key,
+                    kept = %existing.URL,
+                    dropped = %dropped.URL,
+                    "duplicate post removed"
+                );
+            }
+            None => {
+                index_by_key.insert(key, kept.len());
+                kept.push(post);
+            }
+        }
+    }
+
+    kept
+}
+
+/// Prints one line per post added by this run.
+pub fn summarize_new_posts(new_posts: &[Post]) {
+    if new_posts.is_empty() {
+        println!("No new posts since the last run");
+        return;
+    }
+
+    println!("{} new posts since the last run:", new_posts.len());
+    for post in new_posts {
+        println!(
+            "  {} | {} | {}",
+            post.date
+                .map_or_else(|| "undated".to_string(), |date| date.to_string()),
+            html_to_text(&post.title),
+            post.URL
+        );
+        info!(url = %post.URL, title = %post.title, "new post");
+    }
+}
+
+pub fn find_duplicates(backup: &[Post]) {
     print!("Chcking for duplicate post ids...");
     let mut id_counts = HashMap::new();
 
@@ -117,28 +467,93 @@ pub fn find_duplicates(backup: &[Post], logfile: Arc<Mutex<File>>) {
             "{} duplicates found, see log for details",
             duplicates.len()
         );
-        let mut log = logfile.lock().unwrap();
         for (id, count) in duplicates {
-            writeln!(log, "[DUPLICATE] ID: {} was found {} times", id, count).ok();
+            warn!(id = %id, count, "duplicate post id");
         }
     }
-}
 
-pub fn find_missing_ids(backup: &[Post], logfile: Arc<Mutex<File>>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Checking for posts with missing ids...");
+    print!("Checking for posts archived more than once...");
+    let mut urls: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for post in backup {
+        urls.entry(canonical_key(post)).or_default().push(&post.URL);
+    }
+    urls.retain(|_, copies| copies.len() > 1);
 
-    let ids: Vec<usize> = backup.iter()
-        .filter_map(|post| post.id.as_ref()?.parse::<usize>().ok())
+    if urls.is_empty() {
+        println!("No duplicates found");
+    } else {
+        println!(
+            "{} posts have more than one copy, see log for details",
+            urls.len()
+        );
+        for (url, copies) in urls {
+            warn!(canonical_url = %url, copies = ?copies, "post archived more than once");
+        }
+    }
+}
+
+/// Reports posts whose stored content hash no longer matches their title and text.
+pub fn find_hash_mismatches(backup: &[Post]) {
+    print!("Checking content hashes...");
+    let mismatched: Vec<&Post> = backup
+        .iter()
+        .filter(|post| {
+            post.content_hash
+                .as_ref()
+                .is_some_and(|hash| *hash != content_hash(post))
+        })
         .collect();
 
-    let num_ids = ids.len();
-    let expected_nums: HashSet<_> = (0..=num_ids).collect();
-    let actual_ids: HashSet<_> = ids.into_iter().collect();
-    let mut missing_ids: Vec<_> = expected_nums.difference(&actual_ids).cloned().collect();
+    if mismatched.is_empty() {
+        println!("All stored hashes match");
+    } else {
+        println!(
+            "{} posts do not match their stored hash, see log for details",
+            mismatched.len()
+        );
+        for post in mismatched {
+            warn!(url = %post.URL, "content does not match stored hash");
+        }
+    }
+}
+
+pub fn summarize_labels(backup: &[Post]) {
+    print!("Summarizing post labels...");
+    let mut label_counts: HashMap<&str, usize> = HashMap::new();
+
+    for post in backup {
+        for label in &post.labels {
+            *label_counts.entry(label.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    if label_counts.is_empty() {
+        println!("No labels found");
+        return;
+    }
+
+    let mut frequencies: Vec<_> = label_counts.into_iter().collect();
+    frequencies.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    println!(
+        "{} distinct labels found, see log for details",
+        frequencies.len()
+    );
+    for (label, count) in frequencies {
+        info!(label, count, "label frequency");
+    }
+}
+
+pub fn find_missing_ids(backup: &[Post]) -> Result<(), ScrapeError> {
+    println!("Checking for posts with missing ids...");
+
+    let num_ids = backup.iter()
+        .filter(|post| post.id.as_ref().is_some_and(|id| id.parse::<usize>().is_ok()))
+        .count();
+    let missing_ids = missing_ids(backup);
 
     let mut posts_without_ids: Vec<&Post> = backup.iter().filter(|post| post.id.is_none()).collect();
     sort_backup_asc(&mut posts_without_ids)?;
-    missing_ids.sort();
 
     if missing_ids.is_empty() {
         println!(
@@ -151,19 +566,387 @@ pub fn find_missing_ids(backup: &[Post], logfile: Arc<Mutex<File>>) -> Result<()
             missing_ids.len()
         );
 
-        let mut log = logfile.lock().unwrap();
         for (missing_id, post) in missing_ids.iter().zip(posts_without_ids.iter()) {
-            writeln!(log, "[MISSING] ID: {} may be assigned to post with title {:?}", missing_id, post.title).ok();
+            warn!(missing_id, title = %post.title, "missing id may be assigned to post");
         }
     }
 
     Ok(())
 }
 
-pub fn read_posts_from_file(path: &std::path::Path) -> Result<Vec<Post>, Box<dyn std::error::Error>> {
-    let backup_file = File::open(path)?;
-    let reader = std::io::BufReader::new(backup_file);
-    let posts: Vec<Post> = serde_json::from_reader(reader)?;
+/// Numbers absent from the run of ids a complete archive would have, which
+/// starts at 0 and has one id per numbered post. Sorted ascending.
+pub fn missing_ids(backup: &[Post]) -> Vec<usize> {
+    let ids: Vec<usize> = backup.iter()
+        .filter_map(|post| post.id.as_ref()?.parse::<usize>().ok())
+        .collect();
+
+    let num_ids = ids.len();
+    let expected_nums: HashSet<_> = (0..=num_ids).collect();
+    let actual_ids: HashSet<_> = ids.into_iter().collect();
+    let mut missing_ids: Vec<_> = expected_nums.difference(&actual_ids).cloned().collect();
+    missing_ids.sort();
+    missing_ids
+}
+
+/// Reads a list of URLs, one per line, skipping blank lines and `#` comments.
+pub fn read_url_list(path: &Path) -> Result<Vec<String>, ScrapeError> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Opens a backup for reading, undoing gzip or zstd compression recognized
+/// by its magic number so a renamed file still reads.
+fn open_backup(path: &Path) -> Result<Box<dyn BufRead>, ScrapeError> {
+    let mut file = BufReader::new(File::open(path)?);
+    let head = file.fill_buf()?;
+    Ok(if head.starts_with(&GZIP_MAGIC) {
+        Box::new(BufReader::new(GzDecoder::new(file)))
+    } else if head.starts_with(&ZSTD_MAGIC) {
+        Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?))
+    } else {
+        Box::new(file)
+    })
+}
+
+/// Writes each post to `<dir>/<year>/<slug>.json`. Only files whose contents
+/// changed are rewritten, and files of posts no longer in the archive are
+/// removed, so the directory diffs cleanly under version control.
+pub fn write_post_files(data: &[Post], dir: &Path) -> Result<(), ScrapeError> {
+    let keys: Vec<(String, String)> = data
+        .iter()
+        .map(|post| {
+            let year = post_date(post)
+                .map(|date| date.year().to_string())
+                .unwrap_or_else(|| "undated".to_string());
+            (year, post_slug(post))
+        })
+        .collect();
+    let mut occurrences: HashMap<&(String, String), usize> = HashMap::new();
+    for key in &keys {
+        *occurrences.entry(key).or_default() += 1;
+    }
+
+    let mut kept: HashSet<PathBuf> = HashSet::new();
+    let mut written = 0;
+    for (post, key) in data.iter().zip(&keys) {
+        // Posts sharing a slug get a suffix derived from their URL rather
+        // than their position, so names stay stable between runs.
+        let (year, slug) = key;
+        let name = if occurrences[key] > 1 {
+            format!("{}-{}", slug, &sha256_hex(post.URL.as_bytes())[..8])
+        } else {
+            slug.clone()
+        };
+        let path = dir.join(year).join(format!("{}.json", name));
+
+        let json = serde_json::to_string_pretty(post)? + "\n";
+        if fs::read_to_string(&path).ok().as_deref() != Some(json.as_str()) {
+            fs::create_dir_all(dir.join(year))?;
+            fs::write(&path, json)?;
+            written += 1;
+        }
+        kept.insert(path);
+    }
+
+    let mut removed = 0;
+    for path in post_files(dir)? {
+        if !kept.contains(&path) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+
+    println!(
+        "{} posts in {} ({} files written, {} removed)",
+        data.len(),
+        dir.display(),
+        written,
+        removed
+    );
+    Ok(())
+}
+
+/// Every post file in a per-post directory, in path order. Only
+/// `<year>/<name>.json` and `undated/<name>.json` count, so other JSON files
+/// kept in the same directory are never read as posts or removed.
+fn post_files(dir: &Path) -> Result<Vec<PathBuf>, ScrapeError> {
+    let years = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    for year in years {
+        let year = year?.path();
+        let is_year_dir = year.file_name().and_then(|name| name.to_str()).is_some_and(|name| {
+            name == "undated" || (name.len() == 4 && name.chars().all(|c| c.is_ascii_digit()))
+        });
+        if !is_year_dir || !year.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&year)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|extension| extension == "json") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads a backup written in any `BackupFormat`: CBOR by its self-describe
+/// tag, JSON and JSON Lines by their first character, MessagePack otherwise.
+/// Posts are deserialized as they are read, without buffering the file.
+pub fn read_posts_from_file(path: &std::path::Path) -> Result<Vec<Post>, ScrapeError> {
+    let mut posts = read_raw_posts(path)?;
+    migrate_posts(&mut posts)?;
+    Ok(posts)
+}
+
+fn read_raw_posts(path: &std::path::Path) -> Result<Vec<Post>, ScrapeError> {
+    if path.is_dir() {
+        return post_files(path)?
+            .iter()
+            .map(|file| Ok(serde_json::from_reader(BufReader::new(File::open(file)?))?))
+            .collect();
+    }
+    let mut reader = open_backup(path)?;
+    let binary_error = |e: &dyn std::fmt::Display| ScrapeError::Binary(e.to_string());
+    let head = reader.fill_buf()?;
+    if head.starts_with(&CBOR_MAGIC) {
+        reader.consume(CBOR_MAGIC.len());
+        return ciborium::from_reader(reader).map_err(|e| binary_error(&e));
+    }
+    let posts = match head.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') => serde_json::Deserializer::from_reader(reader)
+            .into_iter::<Post>()
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(b'[') | None => serde_json::from_reader(reader)?,
+        Some(_) => rmp_serde::from_read(reader).map_err(|e| binary_error(&e))?,
+    };
 
     Ok(posts)
 }
+
+/// Upgrades from each schema version to the next, indexed by the version they
+/// upgrade from. Fields that were only added rely on serde defaults; entries
+/// here fill in values that can be derived from what the old post already has.
+const MIGRATIONS: &[fn(&mut Post)] = &[
+    // 0 -> 1: backups from before versioning may lack content hashes
+    |post| {
+        if post.content_hash.is_none() {
+            post.content_hash = Some(content_hash(post));
+        }
+    },
+    // 1 -> 2: date became an ISO date, with the header text moved to date_raw
+    |post| {
+        if let Some(text) = post.stored_date.take() {
+            post.date = dates::parse_date_header(&text, DateLocale::Auto);
+            if post.date.is_none() {
+                warn!(url = %post.URL, date = %text, "unrecognized post date, leaving it empty");
+            }
+            post.date_raw.get_or_insert(text);
+        }
+    },
+    // 2 -> 3: added author, published and updated, which only a re-scrape can fill
+    |_| {},
+    // 3 -> 4: added the description and Open Graph fields, likewise
+    |_| {},
+    // 4 -> 5: added blocks, which can be rebuilt from stored HTML
+    |post| {
+        if post.blocks.is_empty() {
+            if let Some(html) = &post.content_html {
+                post.blocks = convert::html_to_blocks(Html::parse_fragment(html).root_element());
+            }
+        }
+    },
+    // 5 -> 6: image blocks gained captions; rebuild them where HTML was kept
+    |post| {
+        if let Some(html) = &post.content_html {
+            post.blocks = convert::html_to_blocks(Html::parse_fragment(html).root_element());
+        }
+    },
+    // 6 -> 7: added embeds. Stored HTML has its iframes sanitized away, so
+    // only a re-scrape can fill them
+    |_| {},
+    // 7 -> 8: added attachments, whose links survive in stored HTML
+    |post| {
+        if let Some(html) = &post.content_html {
+            let fragment = Html::parse_fragment(html);
+            post.attachments = attachments::extract_attachments(fragment.root_element(), &post.URL);
+        }
+    },
+    // 8 -> 9: added outbound links, likewise recoverable from stored HTML
+    |post| {
+        if let Some(html) = &post.content_html {
+            let fragment = Html::parse_fragment(html);
+            post.links = links::extract_links(fragment.root_element(), &post.URL);
+        }
+    },
+    // 9 -> 10: added outgoing and incoming post references. Outgoing ones
+    // come from stored HTML; migrate_posts then links up the whole archive
+    |post| {
+        if let Some(html) = &post.content_html {
+            let fragment = Html::parse_fragment(html);
+            post.outgoing = links::extract_post_references(fragment.root_element(), &post.URL);
+        }
+    },
+    // 10 -> 11: added word_count, reading_minutes and excerpt
+    |post| fill_reading_stats(post, DEFAULT_EXCERPT_WORDS),
+    // 11 -> 12: added id_assigned_manually, which defaults to false
+    |_| {},
+];
+
+/// Version written by this build. Bump it by appending to MIGRATIONS
+/// whenever the post model changes.
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Brings posts read from older backups up to SCHEMA_VERSION, refusing posts
+/// written by a newer build rather than silently dropping fields it doesn't know.
+pub fn migrate_posts(posts: &mut [Post]) -> Result<(), ScrapeError> {
+    let mut migrated = 0;
+    for post in posts.iter_mut() {
+        if post.schema_version > SCHEMA_VERSION {
+            return Err(ScrapeError::UnsupportedSchema {
+                url: post.URL.clone(),
+                found: post.schema_version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        if post.schema_version < SCHEMA_VERSION {
+            for migration in &MIGRATIONS[post.schema_version as usize..] {
+                migration(post);
+            }
+            post.schema_version = SCHEMA_VERSION;
+            migrated += 1;
+        }
+        if let Some(text) = post.stored_date.take() {
+            post.date = NaiveDate::parse_from_str(&text, "%Y-%m-%d").ok();
+            if post.date.is_none() {
+                warn!(url = %post.URL, date = %text, "invalid post date, leaving it empty");
+            }
+        }
+    }
+    if migrated > 0 {
+        links::link_posts(posts);
+        info!(
+            posts = migrated,
+            version = SCHEMA_VERSION,
+            "migrated posts from an older schema"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(url: &str, content_html: &str) -> Post {
+        Post {
+            content: "Some words in the body".to_string(),
+            content_html: Some(content_html.to_string()),
+            ..Post::fixture(url)
+        }
+    }
+
+    #[test]
+    fn canonicalize_strips_query_and_fragment() {
+        assert_eq!(
+            canonicalize_post_url("https://x.blogspot.com/2024/03/a.html?m=1#comments"),
+            "https://x.blogspot.com/2024/03/a.html"
+        );
+    }
+
+    #[test]
+    fn canonicalize_maps_country_domains() {
+        assert_eq!(
+            canonicalize_post_url("http://x.blogspot.de/2024/03/a.html"),
+            "https://x.blogspot.com/2024/03/a.html"
+        );
+        assert_eq!(
+            canonicalize_post_url("https://x.blogspot.com.br/2024/03/a.html?m=0"),
+            "https://x.blogspot.com/2024/03/a.html"
+        );
+    }
+
+    #[test]
+    fn canonicalize_leaves_other_urls_alone() {
+        assert_eq!(
+            canonicalize_post_url("https://example.org/2024/03/a.html"),
+            "https://example.org/2024/03/a.html"
+        );
+        assert_eq!(canonicalize_post_url("not a url"), "not a url");
+    }
+
+    #[test]
+    fn migrate_fills_derived_fields_and_links() {
+        let mut posts = vec![
+            post(
+                "https://x.blogspot.com/2024/03/a.html",
+                r#"<p>See <a href="https://x.blogspot.de/2024/03/b.html?m=1">b</a> and <a href="https://www.rust-lang.org/">Rust</a></p>"#,
+            ),
+            post("https://x.blogspot.com/2024/03/b.html", "<p>Some words in the body</p>"),
+        ];
+        migrate_posts(&mut posts).unwrap();
+
+        for post in &posts {
+            assert_eq!(post.schema_version, SCHEMA_VERSION);
+            assert!(post.content_hash.is_some());
+            assert_eq!(post.word_count, 5);
+            assert!(!post.blocks.is_empty());
+        }
+        assert_eq!(posts[0].links, ["https://www.rust-lang.org/"]);
+        assert_eq!(posts[0].outgoing, ["https://x.blogspot.com/2024/03/b.html"]);
+        assert_eq!(posts[1].incoming, ["https://x.blogspot.com/2024/03/a.html"]);
+    }
+
+    #[test]
+    fn migrate_keeps_legacy_date_text() {
+        let json = r#"[
+            {"title": "Old", "content": "", "URL": "https://x.blogspot.com/2024/03/a.html",
+             "images": [], "date": "Sunday, March 3, 2024"},
+            {"title": "Older", "content": "", "URL": "https://x.blogspot.com/2024/03/b.html",
+             "images": [], "date": "sometime in spring"}
+        ]"#;
+        let mut posts: Vec<Post> = serde_json::from_str(json).unwrap();
+        migrate_posts(&mut posts).unwrap();
+
+        assert_eq!(posts[0].date, NaiveDate::from_ymd_opt(2024, 3, 3));
+        assert_eq!(posts[0].date_raw.as_deref(), Some("Sunday, March 3, 2024"));
+        assert_eq!(posts[1].date, None);
+        assert_eq!(posts[1].date_raw.as_deref(), Some("sometime in spring"));
+    }
+
+    #[test]
+    fn dates_survive_a_round_trip() {
+        let mut posts = vec![post("https://x.blogspot.com/2024/03/a.html", "")];
+        posts[0].date = NaiveDate::from_ymd_opt(2024, 3, 3);
+        migrate_posts(&mut posts).unwrap();
+
+        let json = serde_json::to_string(&posts).unwrap();
+        assert!(json.contains(r#""date":"2024-03-03""#));
+        let mut read: Vec<Post> = serde_json::from_str(&json).unwrap();
+        migrate_posts(&mut read).unwrap();
+        assert_eq!(read[0].date, NaiveDate::from_ymd_opt(2024, 3, 3));
+        assert_eq!(read[0].date_raw, None);
+    }
+
+    #[test]
+    fn migrate_rejects_newer_schema() {
+        let mut newer = post("https://x.blogspot.com/2024/03/a.html", "");
+        newer.schema_version = SCHEMA_VERSION + 1;
+        let err = migrate_posts(&mut [newer]).unwrap_err();
+        assert!(matches!(
+            err,
+            ScrapeError::UnsupportedSchema { found, supported, .. }
+                if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+        ));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::convert;
+use crate::error::ScrapeError;
+use crate::fetcher::Fetcher;
+use crate::helpers;
+use crate::{GraphFormat, LinkCheckArgs, Post};
+use chrono::NaiveDate;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use reqwest::Url;
+use scraper::{ElementRef, Selector};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Delay before the first retry of a link that failed with a transient error.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Finds the http(s) links in a post body that lead off the blog, resolved
+/// against the post URL, without duplicates and in the order they appear.
+pub fn extract_links(body: ElementRef, post_url: &str) -> Vec<String> {
+    let blog_host = canonical_host(post_url);
+    let mut links: Vec<String> = Vec::new();
+    for url in body_links(body, post_url) {
+        if canonical_host(url.as_str()) == blog_host {
+            continue;
+        }
+        if !links.iter().any(|seen| seen == url.as_str()) {
+            links.push(url.to_string());
+        }
+    }
+    links
+}
+
+/// Finds the links in a post body to other posts on the same blog, as
+/// canonical URLs, without duplicates and in the order they appear.
+pub fn extract_post_references(body: ElementRef, post_url: &str) -> Vec<String> {
+    let Ok(permalink) = helpers::post_link_regex(post_url) else {
+        return Vec::new();
+    };
+    let own_url = helpers::canonicalize_post_url(post_url);
+    let blog_host = canonical_host(post_url);
+    let mut references: Vec<String> = Vec::new();
+    for mut url in body_links(body, post_url) {
+        if canonical_host(url.as_str()) != blog_host {
+            continue;
+        }
+        // Older posts link over http; match the scheme the blog was scraped with
+        let _ = url.set_scheme(if post_url.starts_with("http://") {
+            "http"
+        } else {
+            "https"
+        });
+        let reference = helpers::canonicalize_post_url(url.as_str());
+        if permalink.is_match(&reference)
+            && reference != own_url
+            && !references.contains(&reference)
+        {
+            references.push(reference);
+        }
+    }
+    references
+}
+
+/// Fills in `incoming` on every post from the `outgoing` references of the
+/// others. References to posts that are not in the archive are left out.
+pub fn link_posts(posts: &mut [Post]) {
+    let index_by_url = helpers::archive_index(posts);
+
+    let mut incoming: Vec<Vec<String>> = vec![Vec::new(); posts.len()];
+    for (source, post) in posts.iter().enumerate() {
+        for reference in &post.outgoing {
+            match index_by_url.get(reference) {
+                Some(&target) if target != source && !incoming[target].contains(&post.URL) => {
+                    incoming[target].push(post.URL.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+    for (post, mut incoming) in posts.iter_mut().zip(incoming) {
+        incoming.sort();
+        post.incoming = incoming;
+    }
+}
+
+/// Every http(s) link in a post body, resolved against the post URL and
+/// without its fragment.
+fn body_links(body: ElementRef, post_url: &str) -> Vec<Url> {
+    let Ok(selector) = Selector::parse("a[href]") else {
+        return Vec::new();
+    };
+    let base = Url::parse(post_url).ok();
+    body.select(&selector)
+        .filter_map(|link| link.value().attr("href"))
+        .filter_map(|href| {
+            base.as_ref()
+                .and_then(|base| base.join(href.trim()).ok())
+                .or_else(|| Url::parse(href.trim()).ok())
+        })
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        })
+        .collect()
+}
+
+/// Host of a URL once country redirects such as `.blogspot.de` are undone.
+fn canonical_host(url: &str) -> Option<String> {
+    Url::parse(&helpers::canonicalize_post_url(url))
+        .ok()?
+        .host_str()
+        .map(str::to_string)
+}
+
+/// Outcome of checking one link: the final HTTP status, or the error that
+/// prevented getting one.
+#[derive(Serialize, Debug)]
+struct DeadLink {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Posts that link here
+    posts: Vec<String>,
+}
+
+/// Checks every external link recorded in a backup and prints the ones that
+/// no longer answer, with the posts that reference them.
+pub fn verify_links(args: &LinkCheckArgs) -> Result<(), ScrapeError> {
+    let posts = helpers::read_posts_from_file(&args.input)?;
+    let referrers = link_referrers(&posts);
+    println!(
+        "Checking {} links from {} posts in {}",
+        referrers.len(),
+        posts.len(),
+        args.input.display()
+    );
+
+    let fetcher = Fetcher::new(&args.http)?;
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()
+        .map_err(|e| ScrapeError::Config(e.to_string()))?;
+    let results: Vec<(&String, Result<u16, ScrapeError>)> = pool.install(|| {
+        referrers
+            .par_iter()
+            .map(|(url, _)| (*url, check_with_retries(&fetcher, url, args.max_retries)))
+            .collect()
+    });
+
+    let mut dead = Vec::new();
+    for (url, result) in results {
+        let (status, error) = match result {
+            Ok(status) if status < 400 => continue,
+            Ok(status) => (Some(status), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        dead.push(DeadLink {
+            url: url.clone(),
+            status,
+            error,
+            posts: referrers[url].iter().map(|url| url.to_string()).collect(),
+        });
+    }
+
+    for link in &dead {
+        match link.status {
+            Some(status) => println!("{} {}", status, link.url),
+            None => println!(
+                "ERR {} ({})",
+                link.url,
+                link.error.as_deref().unwrap_or_default()
+            ),
+        }
+        for post in &link.posts {
+            println!("    linked from {}", post);
+        }
+    }
+    println!("{} of {} links are dead", dead.len(), referrers.len());
+
+    if let Some(output) = &args.output {
+        fs::write(output, serde_json::to_string_pretty(&dead)?)?;
+        info!(path = %output.display(), links = dead.len(), "wrote dead link report");
+    }
+
+    Ok(())
+}
+
+/// Every linked URL with the posts that link to it, in a stable order.
+fn link_referrers(posts: &[Post]) -> BTreeMap<&String, Vec<&str>> {
+    let mut referrers: BTreeMap<&String, Vec<&str>> = BTreeMap::new();
+    for post in posts {
+        for link in &post.links {
+            referrers.entry(link).or_default().push(&post.URL);
+        }
+    }
+    referrers
+}
+
+fn check_with_retries(fetcher: &Fetcher, url: &str, max_retries: u32) -> Result<u16, ScrapeError> {
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        let result = fetcher.check_status(url);
+        // Server errors are often momentary, so they get the same retries as
+        // network failures; anything else is the link's real answer
+        let retryable = match &result {
+            Ok(status) => status.is_server_error(),
+            Err(e) => e.is_retryable(),
+        };
+        if attempts >= max_retries || !retryable {
+            return result.map(|status| status.as_u16());
+        }
+        let delay = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempts - 1));
+        warn!(
+            url,
+            attempt = attempts,
+            "failed to check link, retrying after delay"
+        );
+        thread::sleep(delay);
+    }
+}
+
+#[derive(Serialize)]
+struct GraphNode<'a> {
+    url: &'a str,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+struct GraphEdge<'a> {
+    from: &'a str,
+    to: &'a str,
+}
+
+#[derive(Serialize)]
+struct LinkGraph<'a> {
+    nodes: Vec<GraphNode<'a>>,
+    edges: Vec<GraphEdge<'a>>,
+}
+
+/// Renders the links between archived posts as Graphviz DOT or JSON. Posts
+/// that neither link to nor are linked from another post are left out.
+pub fn render_graph(posts: &mut [Post], format: GraphFormat) -> Result<String, ScrapeError> {
+    link_posts(posts);
+
+    let mut edges: Vec<GraphEdge> = posts
+        .iter()
+        .flat_map(|post| {
+            post.incoming.iter().map(|source| GraphEdge {
+                from: source,
+                to: &post.URL,
+            })
+        })
+        .collect();
+    edges.sort_by_key(|edge| (edge.from, edge.to));
+    let nodes: Vec<GraphNode> = posts
+        .iter()
+        .filter(|post| {
+            edges
+                .iter()
+                .any(|edge| edge.from == post.URL || edge.to == post.URL)
+        })
+        .map(|post| GraphNode {
+            url: &post.URL,
+            title: convert::html_to_text(&post.title),
+            date: post.date,
+        })
+        .collect();
+
+    match format {
+        GraphFormat::Json => Ok(serde_json::to_string_pretty(&LinkGraph { nodes, edges })?),
+        GraphFormat::Dot => {
+            let mut out = String::from("digraph posts {\n    rankdir=LR;\n    node [shape=box];\n");
+            for node in &nodes {
+                let label = match node.date {
+                    Some(date) => format!("{}\n{}", node.title, date),
+                    None => node.title.clone(),
+                };
+                out.push_str(&format!(
+                    "    {} [label={}];\n",
+                    dot_string(node.url),
+                    dot_string(&label)
+                ));
+            }
+            for edge in &edges {
+                out.push_str(&format!(
+                    "    {} -> {};\n",
+                    dot_string(edge.from),
+                    dot_string(edge.to)
+                ));
+            }
+            out.push('}');
+            Ok(out)
+        }
+    }
+}
+
+fn dot_string(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
@@ -1,12 +1,52 @@
+mod assign;
+mod attachments;
+mod cache;
+mod concurrency;
+mod config;
+mod convert;
+mod dates;
+mod embeds;
+mod error;
+mod export;
+mod fetcher;
 mod helpers;
+mod links;
+mod notify;
+mod pdf;
+mod profile;
+mod report;
 mod scrapers;
+mod search;
+mod similarity;
+mod site;
+mod stats;
+mod warc;
 
-use clap::Parser;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use error::ScrapeError;
+use notify::NotifyArgs;
+use profile::SelectorProfile;
 
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::collections::BTreeSet;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info, info_span};
+
+/// Number of posts written to the --recents view, roughly one home page.
+const RECENT_POSTS_VIEW_SIZE: usize = 20;
+
+/// How often a sleeping --watch loop checks whether Ctrl+C was pressed.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Parser, Debug, Clone)]
 #[command(
@@ -15,69 +55,1336 @@ use std::time::Instant;
     about = "Scrapes posts from a Blogger website"
 )]
 struct Cli {
-    /// Sets the number of threads to use when scraping all post links
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    log: LogArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+struct LogArgs {
+    /// Path of the log file written during the run
+    #[arg(long, global = true, default_value = "scrape_blogger.txt")]
+    log_file: PathBuf,
+
+    /// Minimum level (or tracing filter directive) recorded in the log file
+    #[arg(long, global = true, default_value = "info")]
+    log_level: String,
+
+    /// Format of log file entries
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupFormat {
+    /// A pretty-printed JSON array
+    Json,
+    /// One JSON object per line (JSON Lines)
+    Jsonl,
+    /// MessagePack, a compact binary encoding
+    Msgpack,
+    /// CBOR, a compact binary encoding
+    Cbor,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputLayout {
+    /// Every post in one file
+    Single,
+    /// One pretty-printed JSON file per post, as <year>/<slug>.json under the output directory
+    PerPost,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum DateLocale {
+    /// Month names in any supported language; numeric dates month first
+    Auto,
+    /// English
+    En,
+    /// Spanish
+    Es,
+    /// Portuguese
+    Pt,
+    /// French
+    Fr,
+    /// German
+    De,
+    /// Italian
+    It,
+    /// Dutch
+    Nl,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum GraphFormat {
+    /// Graphviz DOT, for rendering with `dot -Tsvg`
+    Dot,
+    /// A JSON object with `nodes` and `edges` arrays
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Discovery {
+    /// Follows the "Older Posts" links page by page
+    Pager,
+    /// Reads every post URL from the blog's sitemap.xml
+    Sitemap,
+    /// Walks one archive listing per year in parallel using --threads workers
+    Windows,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Scrapes posts from the blog and writes them to a backup file
+    Scrape(Box<ScrapeArgs>),
+
+    /// Exports a previously scraped backup to another format
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+
+    /// Checks a backup file for duplicate and missing post ids
+    #[command(args_conflicts_with_subcommands = true)]
+    Verify {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        #[command(subcommand)]
+        check: Option<VerifyCheck>,
+    },
+
+    /// Walks through posts without an id, suggesting the missing ids, and saves the ids you confirm
+    AssignIds {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Backup file to write the posts to [default: the input file]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Number of previous output files to keep as file.1, file.2, ... (0 disables rotation)
+        #[arg(long, default_value_t = 3)]
+        keep_backups: usize,
+
+        /// Format of the output file
+        #[arg(long, value_enum, default_value_t = BackupFormat::Json)]
+        format: BackupFormat,
+
+        /// Layout of the output; with per-post, --output names a directory that is updated in place
+        #[arg(long, value_enum, default_value_t = OutputLayout::Single)]
+        output_layout: OutputLayout,
+    },
+
+    /// Rebuilds a backup file from post snapshots saved with scrape --snapshot-dir, without using the network
+    Reparse {
+        /// Directory of snapshots written by scrape --snapshot-dir
+        #[arg(long, value_name = "DIR")]
+        snapshot_dir: PathBuf,
+
+        /// Backup file to write the parsed posts to [default: backup.json, or posts/ with --output-layout per-post]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Number of previous output files to keep as file.1, file.2, ... (0 disables rotation)
+        #[arg(long, default_value_t = 3)]
+        keep_backups: usize,
+
+        /// Format of the output file
+        #[arg(long, value_enum, default_value_t = BackupFormat::Json)]
+        format: BackupFormat,
+
+        /// Layout of the output; with per-post, --output names a directory that is updated in place
+        #[arg(long, value_enum, default_value_t = OutputLayout::Single)]
+        output_layout: OutputLayout,
+
+        #[command(flatten)]
+        content: ContentArgs,
+    },
+
+    /// Builds a full-text search index over the title, content and labels of a backup
+    Index {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Directory to write the index to [default: <input>.index]
+        #[arg(long, value_name = "DIR")]
+        index_dir: Option<PathBuf>,
+    },
+
+    /// Searches a backup for posts matching a query, using the index built by the index subcommand when present
+    Search {
+        /// Words to look for; with an index, tantivy query syntax such as "title:gnosis" or "a AND b" is supported
+        query: String,
+
+        /// Backup file to search when no index exists
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Index directory to query [default: <input>.index]
+        #[arg(long, value_name = "DIR")]
+        index_dir: Option<PathBuf>,
+
+        /// Maximum number of results to print
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// Prints summary statistics about a backup file
+    #[command(args_conflicts_with_subcommands = true)]
+    Stats {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Prints the statistics as a JSON object
+        #[arg(long)]
+        json: bool,
+
+        #[command(subcommand)]
+        view: Option<StatsView>,
+    },
+
+    /// Prints the JSON Schema of one post in a backup; JSON backups are an array of these
+    Schema {
+        /// Writes the schema to this file instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum StatsView {
+    /// Prints the graph of links between archived posts
+    Graph {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Format of the graph
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// Writes the graph to this file instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum VerifyCheck {
+    /// Checks the external links recorded in a backup and reports the ones that are dead
+    Links(LinkCheckArgs),
+
+    /// Finds posts whose content is nearly identical, such as re-publishes and lightly edited copies
+    NearDuplicates {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Minimum similarity, from 0 to 1, for a pair of posts to be reported
+        #[arg(long, value_parser = similarity::parse_threshold, default_value_t = 0.9)]
+        threshold: f64,
+
+        /// Also writes the pairs found, with their similarity and shared content, to this JSON file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+struct LinkCheckArgs {
+    /// Backup file to read posts from
+    #[arg(short, long, default_value = "backup.json")]
+    input: PathBuf,
+
+    /// Number of links checked at the same time
+    #[arg(short, long, default_value_t = 8)]
+    threads: usize,
+
+    /// Number of attempts made for each link before reporting it as dead
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+
+    /// Also writes the dead links, with their status codes and linking posts, to this JSON file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    http: HttpArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+struct ScrapeArgs {
+    /// Home page of the blog to scrape; custom domains hosted on Blogger are supported
+    #[arg(
+        long,
+        default_value = "https://gnosticesotericstudyworkaids.blogspot.com/"
+    )]
+    url: String,
+
+    /// Archive of previously scraped posts, read at the start and rewritten by full scrapes [default: backup.json, or posts/ with --output-layout per-post]
+    #[arg(long, value_name = "FILE")]
+    backup: Option<PathBuf>,
+
+    /// Scrapes every blog listed in the --config file instead of a single --url
+    #[arg(long, conflicts_with_all = ["url", "label", "backup"])]
+    all: bool,
+
+    /// Keeps running and repeats a --recent-only scrape every --interval, merging new posts into the backup
+    #[arg(long)]
+    watch: bool,
+
+    /// Time between scrapes in --watch mode, such as "30m", "6h" or "1d"
+    #[arg(long, value_parser = config::parse_interval, default_value = "6h")]
+    interval: Duration,
+
+    /// Multi-blog configuration read by --all
+    #[arg(long, value_name = "FILE", default_value = "blogs.toml")]
+    config: PathBuf,
+
+    /// Number of threads fetching and parsing posts; --max-requests limits how many of them download at once
     #[arg(short, long, default_value_t = 4)]
     threads: usize,
 
-    /// Scrapes only recent posts from the blog homepage without clicking 'Older Posts'
+    /// Always allows --max-requests requests at once instead of lowering the limit while requests are failing
+    #[arg(long)]
+    fixed_concurrency: bool,
+
+    /// Scrapes only recent posts from the blog homepage without clicking 'Older Posts', merging them into the backup
     #[arg(short, long)]
     recent_only: bool,
+
+    /// Where --recent-only writes a view of the newest posts in the backup
+    #[arg(long, value_name = "FILE", default_value = "recents.json")]
+    recents: PathBuf,
+
+    #[command(flatten)]
+    content: ContentArgs,
+
+    /// Scrapes the latest Internet Archive snapshot of posts that have been deleted from the blog
+    #[arg(long)]
+    wayback_fallback: bool,
+
+    /// Looks up preview images for embedded YouTube and Vimeo videos
+    #[arg(long)]
+    embed_thumbnails: bool,
+
+    /// Downloads linked PDFs, documents and Google Drive files into this directory
+    #[arg(long, value_name = "DIR")]
+    download_attachments: Option<PathBuf>,
+
+    /// Saves the raw HTML of every scraped post to this directory for the reparse command
+    #[arg(long, value_name = "DIR")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Saves in-progress results to a checkpoint file every N scraped posts (0 disables)
+    #[arg(long, default_value_t = 50)]
+    checkpoint_every: usize,
+
+    /// Crawls only the posts tagged with this label, merging them into the backup and
+    /// writing them to label-<name>.json (a label-<name> directory with the per-post layout)
+    #[arg(long)]
+    label: Option<String>,
+
+    /// How post links are discovered when scraping the whole blog
+    #[arg(long, value_enum, default_value_t = Discovery::Pager)]
+    discovery: Discovery,
+
+    /// Re-fetches these post URLs and replaces their archived entries instead of discovering new posts
+    #[arg(long, value_name = "URL", num_args = 1..)]
+    refresh: Vec<String>,
+
+    /// Reads post URLs to re-fetch from a file, one per line (blank lines and # comments are ignored)
+    #[arg(long, value_name = "FILE")]
+    refresh_from: Option<PathBuf>,
+
+    /// Where the posts that failed to scrape are listed; removed after a run without failures
+    #[arg(long, value_name = "FILE", default_value = "failed_urls.json")]
+    failed_urls: PathBuf,
+
+    /// Retries only the posts listed in --failed-urls and merges the ones that succeed into the backup
+    #[arg(long, conflicts_with_all = ["refresh", "refresh_from", "recent_only", "label"])]
+    retry_failures: bool,
+
+    /// Re-fetches every archived post as well and updates entries whose title or body changed
+    #[arg(long)]
+    check_updates: bool,
+
+    /// Only archives posts published on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    since: Option<NaiveDate>,
+
+    /// Only archives posts published on or before this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    until: Option<NaiveDate>,
+
+    /// Discovers post links and reports how many would be scraped without fetching posts or writing files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Where the posts that were not in the backup before this run are written
+    #[arg(long, value_name = "FILE", default_value = "new_since_last_run.json")]
+    new_posts: PathBuf,
+
+    /// Path of the machine-readable summary written at the end of the run
+    #[arg(long, default_value = "run_report.json")]
+    report: PathBuf,
+
+    /// Number of previous output files to keep as file.1, file.2, ... (0 disables rotation)
+    #[arg(long, default_value_t = 3)]
+    keep_backups: usize,
+
+    /// Format of the backup, recents and new-posts files; any format is accepted when reading, and names ending in .gz or .zst are compressed
+    #[arg(long, value_enum, default_value_t = BackupFormat::Json)]
+    format: BackupFormat,
+
+    /// Layout of the backup; with per-post, --backup names a directory that is updated in place
+    #[arg(long, value_enum, default_value_t = OutputLayout::Single)]
+    output_layout: OutputLayout,
+
+    /// Number of attempts made for each post before giving up
+    #[arg(long, default_value_t = 4)]
+    max_retries: u32,
+
+    /// Delay in milliseconds before the first retry, doubled (with jitter) on each further attempt
+    #[arg(long, default_value_t = 1000)]
+    retry_base_delay: u64,
+
+    #[command(flatten)]
+    notify: NotifyArgs,
+
+    #[command(flatten)]
+    http: HttpArgs,
+}
+
+/// Options controlling how post pages are parsed and which representations
+/// of a post body are stored.
+#[derive(Args, Debug, Clone)]
+struct ContentArgs {
+    /// TOML or JSON file of CSS selectors for blogs using a different Blogger template
+    #[arg(long, value_name = "FILE", value_parser = profile::parse_profile_arg)]
+    profile: Option<SelectorProfile>,
+
+    /// Stores the sanitized HTML of each post body alongside the extracted text
+    #[arg(long)]
+    keep_html: bool,
+
+    /// Stores a Markdown conversion of each post body alongside the extracted text
+    #[arg(short, long)]
+    markdown: bool,
+
+    /// Language of the blog's date headers; posts whose header is missing or
+    /// cannot be read fall back to the timestamp in their markup
+    #[arg(long, value_enum, default_value_t = DateLocale::Auto)]
+    date_locale: DateLocale,
+
+    /// Number of words from the start of each post kept as its excerpt
+    #[arg(long, default_value_t = helpers::DEFAULT_EXCERPT_WORDS)]
+    excerpt_words: usize,
+}
+
+impl ScrapeArgs {
+    fn backup_path(&self) -> PathBuf {
+        self.backup
+            .clone()
+            .unwrap_or_else(|| default_backup_path(self.output_layout))
+    }
+}
+
+impl ContentArgs {
+    fn selectors(&self) -> &SelectorProfile {
+        self.profile.as_ref().unwrap_or(&profile::DEFAULT_PROFILE)
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+struct HttpArgs {
+    /// User-Agent header sent with every request
+    #[arg(long, default_value = concat!("scrape_blogger/", env!("CARGO_PKG_VERSION")))]
+    user_agent: String,
+
+    /// Extra header sent with requests to the blog itself, such as a cookie for a
+    /// private blog, as "Name: value" (may be repeated). Other hosts never see it.
+    #[arg(long = "header", value_name = "HEADER", value_parser = fetcher::parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Time in seconds allowed to establish a connection
+    #[arg(long, default_value_t = 10)]
+    connect_timeout: u64,
+
+    /// Total time in seconds allowed for a single request, including reading the body
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// Maximum number of idle keep-alive connections kept open per host
+    #[arg(long, default_value_t = 16)]
+    pool_size: usize,
+
+    /// Proxy URL (http, https or socks5) for all requests; defaults to HTTPS_PROXY/ALL_PROXY
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Maximum number of HTTP requests in flight at once across all threads [default: one per thread]
+    #[arg(long, value_name = "N")]
+    max_requests: Option<NonZeroUsize>,
+
+    /// Minimum delay in milliseconds between requests, shared across all threads
+    #[arg(long, default_value_t = 0)]
+    delay_ms: u64,
+
+    /// Directory where fetched post pages are cached and re-read from instead of downloading them again
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Records every HTTP request and response to this WARC file (gzipped when it ends in .gz)
+    #[arg(long, value_name = "FILE")]
+    warc: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ExportFormat {
+    /// Builds a single EPUB with one chapter per post, oldest first
+    Epub {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Path of the EPUB file to create
+        #[arg(short, long, default_value = "backup.epub")]
+        output: PathBuf,
+
+        /// Title of the book [default: the blog's host name]
+        #[arg(long)]
+        title: Option<String>,
+
+        #[command(flatten)]
+        http: HttpArgs,
+    },
+
+    /// Builds a static website with a page per post, archive and label indexes, local images and client-side search
+    Site {
+        /// Directory to write the site to
+        dir: PathBuf,
+
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        #[command(flatten)]
+        http: HttpArgs,
+    },
+
+    /// Writes one Markdown file per post with YAML front matter for Hugo or Jekyll
+    Hugo {
+        /// Site directory; posts go to <dir>/content/posts (or <dir>/_posts with --jekyll)
+        dir: PathBuf,
+
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Uses Jekyll's _posts/YYYY-MM-DD-slug.md layout instead of Hugo's
+        #[arg(long)]
+        jekyll: bool,
+    },
+
+    /// Renders posts with their JPEG images to a single PDF with a table of contents, or one PDF per post
+    Pdf {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// PDF file to create, or the directory to write to with --per-post [default: backup.pdf, or pdf/]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Writes one PDF per post instead of a combined volume
+        #[arg(long)]
+        per_post: bool,
+
+        #[command(flatten)]
+        http: HttpArgs,
+    },
+
+    /// Writes one row of metadata per post (id, title, date, URL, word count, labels, image count)
+    Csv {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// CSV file to create
+        #[arg(short, long, default_value = "posts.csv")]
+        output: PathBuf,
+
+        /// Separates columns with tabs instead of commas
+        #[arg(long)]
+        tsv: bool,
+    },
+
+    /// Writes post metadata and content to a Parquet file for DuckDB, Polars and similar tools
+    Parquet {
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+
+        /// Parquet file to create
+        #[arg(short, long, default_value = "posts.parquet")]
+        output: PathBuf,
+    },
+
+    /// Writes one plain-text file per post and an index.txt
+    Txt {
+        /// Directory to write the files to
+        dir: PathBuf,
+
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+    },
+
+    /// Writes one gemtext file per post and an index.gmi for Gemini capsules
+    Gemini {
+        /// Directory to write the files to
+        dir: PathBuf,
+
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+    },
+
+    /// Writes an Obsidian vault with a note per post, wiki-links between posts and an _index note per year
+    Obsidian {
+        /// Vault directory to write the notes to
+        dir: PathBuf,
+
+        /// Backup file to read posts from
+        #[arg(short, long, default_value = "backup.json")]
+        input: PathBuf,
+    },
 }
 
 // TODO it may make sense to implement Ord for Post
 // but sometimes it makes sense to sort by date, others id
 // for now specific functions will be used
+/// One archived blog post
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[cfg_attr(test, derive(Default))]
 struct Post {
+    /// Version of the post model this record was written with; missing in
+    /// backups from before versioning, which read as 0 and are migrated on load
+    #[serde(default)]
+    schema_version: u32,
+    /// Blogger's numeric post id
     id: Option<String>,
+    /// Whether the id was entered with assign-ids rather than read from the title
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    id_assigned_manually: bool,
+    /// Title of the post, with the profile's title prefix removed
     title: String,
+    /// Plain text of the post body
     content: String,
+    /// The post body as an ordered list of paragraphs, headings, lists,
+    /// images, quotes and code
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    blocks: Vec<Block>,
+    /// Sanitized HTML of the post body, stored with --keep-html
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    /// Markdown conversion of the post body, stored with --markdown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_md: Option<String>,
+    /// Number of words in the plain text of the post body
+    #[serde(default)]
+    word_count: usize,
+    /// Estimated time to read the post, rounded up to whole minutes
+    #[serde(default)]
+    reading_minutes: u32,
+    /// The first words of the post body, for listings that show a preview
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    excerpt: Option<String>,
+    /// Address the post was scraped from
     URL: String,
+    /// Canonical address of the post, from rel=canonical or redirects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    canonical_url: Option<String>,
+    /// Publication date, written as YYYY-MM-DD
+    #[serde(default, skip_deserializing)]
+    date: Option<NaiveDate>,
+    /// `date` as read from a backup: an ISO date, or the date header text
+    /// that backups from before schema version 2 stored there. Resolved into
+    /// `date` and `date_raw` by `helpers::migrate_posts`.
+    #[serde(rename = "date", default, skip_serializing)]
+    #[schemars(skip)]
+    stored_date: Option<String>,
+    /// Date header text exactly as displayed on the blog
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date_raw: Option<String>,
+    /// Name of the post's author, from the byline or `<meta name="author">`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    /// RFC 3339 publication time from the post's markup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    published: Option<DateTime<FixedOffset>>,
+    /// RFC 3339 time of the post's last edit on the blog, from its markup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated: Option<DateTime<FixedOffset>>,
+    /// Summary from the page's `<meta name="description">`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Title given for link previews in `<meta property="og:title">`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    og_title: Option<String>,
+    /// Summary given for link previews in `<meta property="og:description">`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    og_description: Option<String>,
+    /// Cover image chosen for link previews
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    og_image: Option<String>,
+    /// Absolute URLs of the images in the post body
+    images: BTreeSet<String>,
+    /// Videos and other frames embedded in the post body
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    embeds: Vec<Embed>,
+    /// Documents the post body links to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Attachment>,
+    /// Links in the post body that lead off the blog
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<String>,
+    /// Canonical URLs of other posts on the blog that this post links to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outgoing: Vec<String>,
+    /// URLs of archived posts that link to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    incoming: Vec<String>,
+    /// Labels the post is filed under, in the order the blog shows them
+    #[serde(default)]
+    labels: Vec<String>,
+    /// Reader comments, oldest first
+    #[serde(default)]
+    comments: Vec<Comment>,
+    /// Hex SHA-256 of the title and content, used to detect edits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+    /// Where the post was scraped from when it was not the live blog, e.g. "wayback"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    /// RFC 3339 time the post was last fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_checked: Option<String>,
+    /// RFC 3339 time the post's content last changed in the archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+#[cfg(test)]
+impl Post {
+    /// A post at `url` with every other field empty, for unit tests.
+    fn fixture(url: &str) -> Post {
+        Post {
+            title: "A post".to_string(),
+            URL: url.to_string(),
+            ..Post::default()
+        }
+    }
+}
+
+/// One structural element of a post body. Text is plain, with whitespace
+/// collapsed and line breaks kept as `\n`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Block {
+    Paragraph {
+        text: String,
+    },
+    Heading {
+        level: u8,
+        text: String,
+    },
+    List {
+        ordered: bool,
+        items: Vec<String>,
+    },
+    Image {
+        src: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alt: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        caption: Option<String>,
+    },
+    Blockquote {
+        text: String,
+    },
+    /// Preformatted text, with its whitespace preserved
+    Code {
+        text: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+struct Embed {
+    /// "youtube", "vimeo", or the host name of any other embedded page
+    provider: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    video_id: Option<String>,
+    url: String,
+    /// Preview image, looked up with scrape --embed-thumbnails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+struct Attachment {
+    url: String,
+    /// File extension such as "pdf", or "google-drive", "google-docs" or "scribd"
+    kind: String,
+    /// Text of the link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    /// Name of the copy saved with scrape --download-attachments, relative to that directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+struct Comment {
+    /// Display name of the commenter, "Anonymous" when none is shown
+    author: String,
+    /// Comment date text exactly as displayed on the blog
     date: Option<String>,
-    images: HashSet<String>,
-} 
+    /// Plain text of the comment
+    body: String,
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
     let args = Cli::parse();
-    let error_written = Arc::new(Mutex::new(false));
-    let log_file = helpers::create_log_file()?;
-    let base_url = "https://gnosticesotericstudyworkaids.blogspot.com/";
-    let search_timer = Instant::now();
-    let mut backup = scrapers::search_and_scrape(
+    if let Err(e) = helpers::init_logging(&args.log) {
+        eprintln!("Error: {}", e);
+        return ExitCode::from(e.exit_code());
+    }
+
+    match run(args.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(command: Command) -> Result<(), ScrapeError> {
+    match command {
+        Command::Scrape(scrape_args) => scrape(*scrape_args),
+        Command::Export { format } => match format {
+            ExportFormat::Epub {
+                input,
+                output,
+                title,
+                http,
+            } => {
+                let fetcher = fetcher::Fetcher::new(&http)?;
+                export::export_epub(&fetcher, &input, &output, title.as_deref())
+            }
+            ExportFormat::Site { dir, input, http } => {
+                let fetcher = fetcher::Fetcher::new(&http)?;
+                site::export_site(&fetcher, &input, &dir)
+            }
+            ExportFormat::Hugo { dir, input, jekyll } => {
+                export::export_markdown_site(&input, &dir, jekyll)
+            }
+            ExportFormat::Obsidian { dir, input } => export::export_obsidian(&input, &dir),
+            ExportFormat::Pdf {
+                input,
+                output,
+                per_post,
+                http,
+            } => {
+                let fetcher = fetcher::Fetcher::new(&http)?;
+                let output = output
+                    .unwrap_or_else(|| PathBuf::from(if per_post { "pdf" } else { "backup.pdf" }));
+                pdf::export_pdf(&fetcher, &input, &output, per_post)
+            }
+            ExportFormat::Csv { input, output, tsv } => export::export_csv(&input, &output, tsv),
+            ExportFormat::Parquet { input, output } => export::export_parquet(&input, &output),
+            ExportFormat::Txt { dir, input } => export::export_text(&input, &dir, false),
+            ExportFormat::Gemini { dir, input } => export::export_text(&input, &dir, true),
+        },
+        Command::Verify { input, check } => match check {
+            None => verify(&input),
+            Some(VerifyCheck::Links(args)) => links::verify_links(&args),
+            Some(VerifyCheck::NearDuplicates {
+                input,
+                threshold,
+                output,
+            }) => similarity::find_near_duplicates(&input, threshold, output.as_deref()),
+        },
+        Command::AssignIds {
+            input,
+            output,
+            keep_backups,
+            format,
+            output_layout,
+        } => {
+            let mut posts = helpers::read_posts_from_file(&input)?;
+            let assigned = assign::assign_ids(&mut posts, io::stdin().lock(), io::stdout())?;
+            if assigned == 0 {
+                println!("No ids were assigned; {} is unchanged", input.display());
+                return Ok(());
+            }
+            let output = output.unwrap_or(input);
+            write_backup(&posts, &output, format, output_layout, keep_backups)?;
+            println!("Assigned {} ids", assigned);
+            Ok(())
+        }
+        Command::Reparse {
+            snapshot_dir,
+            output,
+            keep_backups,
+            format,
+            output_layout,
+            content,
+        } => reparse(
+            &snapshot_dir,
+            &output.unwrap_or_else(|| default_backup_path(output_layout)),
+            keep_backups,
+            format,
+            output_layout,
+            &content,
+        ),
+        Command::Index { input, index_dir } => {
+            let posts = helpers::read_posts_from_file(&input)?;
+            let index_dir = index_dir.unwrap_or_else(|| search::default_index_dir(&input));
+            search::build_index(&posts, &index_dir)
+        }
+        Command::Search {
+            query,
+            input,
+            index_dir,
+            limit,
+        } => {
+            let index_dir = index_dir.unwrap_or_else(|| search::default_index_dir(&input));
+            let hits = search::search(
+                &query,
+                &index_dir,
+                || helpers::read_posts_from_file(&input),
+                limit,
+            )?;
+            search::print_hits(&hits);
+            Ok(())
+        }
+        Command::Stats { input, json, view } => match view {
+            None => stats::print_stats(&input, json),
+            Some(StatsView::Graph {
+                input,
+                format,
+                output,
+            }) => {
+                let mut posts = helpers::read_posts_from_file(&input)?;
+                let graph = links::render_graph(&mut posts, format)?;
+                match output {
+                    Some(path) => Ok(std::fs::write(path, graph + "\n")?),
+                    None => {
+                        println!("{}", graph);
+                        Ok(())
+                    }
+                }
+            }
+        },
+        Command::Schema { output } => {
+            let schema = serde_json::to_string_pretty(&schemars::schema_for!(Post))?;
+            match output {
+                Some(path) => Ok(std::fs::write(path, schema + "\n")?),
+                None => {
+                    println!("{}", schema);
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// Requests allowed in flight at once before any lowering for errors.
+fn request_limit(args: &ScrapeArgs) -> usize {
+    args.http.max_requests.map_or(args.threads, NonZeroUsize::get)
+}
+
+fn scrape(args: ScrapeArgs) -> Result<(), ScrapeError> {
+    let interrupted = helpers::install_interrupt_handler()?;
+    let mut fetcher = fetcher::Fetcher::new(&args.http)?;
+    if let Some(dir) = &args.snapshot_dir {
+        fetcher.save_snapshots_to(dir.clone())?;
+    }
+    fetcher.limit_requests(request_limit(&args), !args.fixed_concurrency);
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()
+        .map_err(|e| ScrapeError::Config(e.to_string()))?;
+
+    if args.watch {
+        watch(&fetcher, &pool, args, interrupted)
+    } else if args.all {
+        scrape_all(&fetcher, &pool, &args, interrupted)
+    } else {
+        scrape_blog(&fetcher, &pool, args, interrupted)
+    }
+}
+
+/// Repeats an incremental scrape every `--interval` until interrupted. Errors
+/// are logged and retried on the next cycle rather than ending the process,
+/// except for a backup that cannot be read: later cycles would fail the same
+/// way, and the archive needs attention before anything is written over it.
+fn watch(
+    fetcher: &fetcher::Fetcher,
+    pool: &ThreadPool,
+    mut args: ScrapeArgs,
+    interrupted: Arc<AtomicBool>,
+) -> Result<(), ScrapeError> {
+    args.recent_only = true;
+    let mut cycle = 0;
+
+    loop {
+        cycle += 1;
+        let _span = info_span!("watch", cycle).entered();
+        info!("starting watch cycle");
+        let result = if args.all {
+            scrape_all(fetcher, pool, &args, interrupted.clone())
+        } else {
+            scrape_blog(fetcher, pool, args.clone(), interrupted.clone())
+        };
+        match result {
+            Err(e @ ScrapeError::Backup { .. }) => {
+                error!(error = %e, "stopping watch mode");
+                return Err(e);
+            }
+            Err(e) => {
+                error!(error = %e, "watch cycle failed");
+                eprintln!("Watch cycle {} failed: {}", cycle, e);
+            }
+            Ok(()) => {}
+        }
+
+        let next_run = Instant::now() + args.interval;
+        println!(
+            "Next scrape in {}s; press Ctrl+C to stop",
+            args.interval.as_secs()
+        );
+        while Instant::now() < next_run {
+            if interrupted.load(Ordering::SeqCst) {
+                println!("Watch mode stopped");
+                return Ok(());
+            }
+            thread::sleep(
+                WATCH_POLL_INTERVAL.min(next_run.saturating_duration_since(Instant::now())),
+            );
+        }
+    }
+}
+
+/// Scrapes every blog listed in the --config file with a shared client and
+/// thread pool. A failing blog does not stop the others.
+fn scrape_all(
+    fetcher: &fetcher::Fetcher,
+    pool: &ThreadPool,
+    args: &ScrapeArgs,
+    interrupted: Arc<AtomicBool>,
+) -> Result<(), ScrapeError> {
+    let config = config::BlogsConfig::load(&args.config)?;
+    let mut first_error = None;
+
+    for blog in config.blogs {
+        if interrupted.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let stem = label_file_stem(&blog.name);
+        let report_path = blog
+            .report
+            .unwrap_or_else(|| per_blog_path(&args.report, &stem));
+        if let Some(schedule) = blog.schedule {
+            if !report::is_due(&report_path, schedule) {
+                println!(
+                    "Skipping {}: last scraped less than {}s ago",
+                    blog.name,
+                    schedule.as_secs()
+                );
+                continue;
+            }
+        }
+
+        println!("Scraping {} ({})", blog.name, blog.url);
+        let mut blog_args = args.clone();
+        blog_args.url = blog.url;
+        blog_args.backup = Some(blog.output);
+        blog_args.report = report_path;
+        blog_args.recents = per_blog_path(&args.recents, &stem);
+        blog_args.new_posts = per_blog_path(&args.new_posts, &stem);
+        blog_args.failed_urls = per_blog_path(&args.failed_urls, &stem);
+        if let Some(profile) = &blog.profile {
+            blog_args.content.profile = Some(SelectorProfile::load(profile)?);
+        }
+
+        let _span = info_span!("blog", name = %blog.name).entered();
+        if let Err(e) = scrape_blog(fetcher, pool, blog_args, interrupted.clone()) {
+            error!(blog = %blog.name, error = %e, "failed to scrape blog");
+            eprintln!("Failed to scrape {}: {}", blog.name, e);
+            first_error.get_or_insert(e);
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// `path` with the blog's name added to the file name, so `recents.json`
+/// becomes `recents-<blog>.json` in the same directory.
+fn per_blog_path(path: &Path, blog: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, blog, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, blog),
+    };
+    path.with_file_name(name)
+}
+
+fn scrape_blog(
+    fetcher: &fetcher::Fetcher,
+    pool: &ThreadPool,
+    args: ScrapeArgs,
+    interrupted: Arc<AtomicBool>,
+) -> Result<(), ScrapeError> {
+    let stats = report::RunStats::new(fetcher);
+    let base_url = helpers::normalize_base_url(&args.url)?;
+    fetcher.send_headers_to(&base_url);
+    let start_url = match &args.label {
+        Some(label) => helpers::label_url(&base_url, label)?,
+        None => base_url.clone(),
+    };
+    if args.dry_run {
+        return scrapers::dry_run(fetcher, pool, &args, interrupted, &start_url);
+    }
+
+    let outcome = scrapers::search_and_scrape(
+        fetcher,
+        pool,
         args.clone(),
-        error_written.clone(),
-        log_file.clone(),
-        base_url,
+        &stats,
+        interrupted.clone(),
+        &start_url,
     )?;
-    let search_duration = search_timer.elapsed();
+    let mut backup = outcome.posts;
+    let search_duration = stats.elapsed();
     let minutes = search_duration.as_secs() / 60;
     let seconds = search_duration.as_secs() % 60;
     println!("Searching and scraping took {:02}:{:02}", minutes, seconds);
+    if args.fixed_concurrency && fetcher.rate_limited_count() > 0 {
+        println!(
+            "Blogger asked the scraper to slow down {} times; consider running with fewer threads",
+            fetcher.rate_limited_count()
+        );
+    }
+    let lowest_concurrency = report::RunStats::count(&stats.lowest_concurrency);
+    if lowest_concurrency < request_limit(&args) {
+        println!(
+            "Requests were failing, so the scraper lowered itself to as few as {} of {} requests at once; see log for details",
+            lowest_concurrency,
+            request_limit(&args)
+        );
+    }
+    let writing_timer = Instant::now();
+    links::link_posts(&mut backup);
     helpers::sort_backup(&mut backup)?;
 
-    let output_file = if args.recent_only {
-        "recents.json"
-    } else {
-        "backup.json"
-    };
-    helpers::write_to_file(&backup, output_file)?;
-
-    let error_written = Arc::try_unwrap(error_written)
-        .unwrap()
-        .into_inner()
-        .unwrap();
-    if error_written {
-        eprintln!("One or more errors ocurred... See log for more information. It may be necessary to re-run using fewer threads");
-    } else {
+    // Posts found through --label are merged into the main backup like any
+    // other, so later runs don't report them as new again
+    let mut output_file = args.backup_path();
+    write_backup(
+        &backup,
+        &output_file,
+        args.format,
+        args.output_layout,
+        args.keep_backups,
+    )?;
+    let mut posts_in_output = backup.len();
+    if let Some(label) = &args.label {
+        let label_posts: Vec<Post> = backup
+            .iter()
+            .filter(|post| {
+                outcome
+                    .discovered
+                    .contains(&helpers::canonicalize_post_url(&post.URL))
+                    || post.labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+            })
+            .cloned()
+            .collect();
+        output_file = label_output_path(&output_file, label, args.output_layout);
+        write_backup(
+            &label_posts,
+            &output_file,
+            args.format,
+            args.output_layout,
+            args.keep_backups,
+        )?;
+        println!(
+            "{} posts filed under {} were written to {}",
+            label_posts.len(),
+            label,
+            output_file.display()
+        );
+        posts_in_output = label_posts.len();
+    }
+    if args.recent_only && args.label.is_none() {
+        let newest = &backup[..backup.len().min(RECENT_POSTS_VIEW_SIZE)];
+        helpers::write_to_file(newest, &args.recents.to_string_lossy(), args.format)?;
+    }
+    let new_posts: Vec<Post> = backup
+        .iter()
+        .filter(|post| {
+            !outcome
+                .previously_archived
+                .contains(&helpers::canonicalize_post_url(&post.URL))
+        })
+        .cloned()
+        .collect();
+    helpers::write_to_file(&new_posts, &args.new_posts.to_string_lossy(), args.format)?;
+    helpers::summarize_new_posts(&new_posts);
+
+    let summary = notify::RunSummary::new(
+        &base_url,
+        &new_posts,
+        &outcome.changed,
+        report::RunStats::count(&stats.failed),
+        interrupted.load(Ordering::SeqCst),
+    );
+    notify::send_notifications(fetcher, &args.notify, &summary);
+    scrapers::clear_checkpoint(&args.backup_path());
+    stats.record_phase("writing", writing_timer.elapsed());
+
+    let was_interrupted = interrupted.load(Ordering::SeqCst);
+    let run_report = stats.report(
+        &output_file.to_string_lossy(),
+        posts_in_output,
+        was_interrupted,
+        fetcher,
+    );
+    report::write_report(&run_report, &args.report)?;
+    report::write_failed_urls(&outcome.failed, &args.failed_urls)?;
+
+    if run_report.failed > 0 {
+        eprintln!("One or more errors ocurred... See log for more information. Failed posts can be retried with --retry-failures");
+    } else if args.fixed_concurrency {
         println!("Be sure to check log file for any warnings. If many WARNS occurred, you may want to run with fewer threads.");
         println!("If no WARNS occurred, you may try increasing the thread pool using -t <num_threads> to speed things up.")
+    } else if lowest_concurrency == request_limit(&args) {
+        println!("Be sure to check log file for any warnings. If no WARNS occurred, you may try increasing the thread pool using -t <num_threads> to speed things up.")
     }
 
-    if !args.recent_only {
-        helpers::find_duplicates(&backup, log_file.clone());
-        helpers::find_missing_ids(&backup, log_file.clone())?;
+    if was_interrupted {
+        println!("The run was interrupted; re-run to scrape the remaining posts");
+        return Ok(());
+    }
+
+    if !args.recent_only && args.label.is_none() {
+        helpers::find_duplicates(&backup);
+        helpers::summarize_labels(&backup);
+        helpers::find_missing_ids(&backup)?;
+    }
+
+    Ok(())
+}
+
+/// Where a --label scrape writes the posts filed under the label: next to
+/// the main backup, as a file or a directory of post files to match it.
+fn label_output_path(backup: &Path, label: &str, layout: OutputLayout) -> PathBuf {
+    let name = format!("label-{}", label_file_stem(label));
+    backup.with_file_name(match layout {
+        OutputLayout::Single => name + ".json",
+        OutputLayout::PerPost => name,
+    })
+}
+
+/// Lowercase words of a label joined by dashes, or a hash of the label when
+/// it has no letters or digits at all.
+fn label_file_stem(label: &str) -> String {
+    let stem = label
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if stem.is_empty() {
+        format!("label-{}", &helpers::sha256_hex(label.as_bytes())[..8])
+    } else {
+        stem
     }
+}
+
+fn verify(input: &Path) -> Result<(), ScrapeError> {
+    let backup = helpers::read_posts_from_file(input)?;
+    println!("Loaded {} posts from {}", backup.len(), input.display());
+
+    helpers::find_duplicates(&backup);
+    helpers::find_missing_ids(&backup)?;
+    helpers::find_hash_mismatches(&backup);
 
     Ok(())
 }
+
+/// Where an archive is written when no path is given: one file, or a
+/// directory of post files with the per-post layout.
+fn default_backup_path(layout: OutputLayout) -> PathBuf {
+    match layout {
+        OutputLayout::Single => PathBuf::from("backup.json"),
+        OutputLayout::PerPost => PathBuf::from("posts"),
+    }
+}
+
+/// Writes a whole archive in the chosen layout. Single files are rotated
+/// first; per-post directories are updated in place and left to version
+/// control for history.
+fn write_backup(
+    posts: &[Post],
+    path: &Path,
+    format: BackupFormat,
+    layout: OutputLayout,
+    keep_backups: usize,
+) -> Result<(), ScrapeError> {
+    match layout {
+        OutputLayout::Single => {
+            let path = path.to_string_lossy();
+            helpers::rotate_backups(&path, keep_backups)?;
+            helpers::write_to_file(posts, &path, format)
+        }
+        OutputLayout::PerPost => helpers::write_post_files(posts, path),
+    }
+}
+
+fn reparse(
+    snapshot_dir: &Path,
+    output: &Path,
+    keep_backups: usize,
+    format: BackupFormat,
+    layout: OutputLayout,
+    content: &ContentArgs,
+) -> Result<(), ScrapeError> {
+    let mut posts = helpers::dedupe_canonical(scrapers::reparse_snapshots(snapshot_dir, content)?);
+    links::link_posts(&mut posts);
+    helpers::sort_backup(&mut posts)?;
+    write_backup(&posts, output, format, layout, keep_backups)
+}
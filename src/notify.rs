@@ -0,0 +1,254 @@
+use crate::convert;
+use crate::error::ScrapeError;
+use crate::fetcher::Fetcher;
+use crate::Post;
+use chrono::{NaiveDate, Utc};
+use clap::Args;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::Serialize;
+use tracing::{info, warn};
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+/// Telegram rejects longer messages; leave room for the "and N more" line.
+const TELEGRAM_MESSAGE_LIMIT: usize = 4000;
+
+/// Where to announce the results of a scrape.
+#[derive(Args, Debug, Clone)]
+pub struct NotifyArgs {
+    /// POSTs a JSON description of new and changed posts to this URL after each run
+    #[arg(long, value_name = "URL")]
+    pub notify_webhook: Option<String>,
+
+    /// SMTP server used to email a summary after each run (STARTTLS)
+    #[arg(long, env = "SMTP_HOST", value_name = "HOST")]
+    pub smtp_host: Option<String>,
+
+    #[arg(long, env = "SMTP_PORT", default_value_t = 587)]
+    pub smtp_port: u16,
+
+    #[arg(long, env = "SMTP_USER", value_name = "USER")]
+    pub smtp_user: Option<String>,
+
+    #[arg(
+        long,
+        env = "SMTP_PASSWORD",
+        value_name = "PASSWORD",
+        hide_env_values = true
+    )]
+    pub smtp_password: Option<String>,
+
+    /// Sender of the summary email, e.g. "Archive <archive@example.com>"
+    #[arg(long, env = "EMAIL_FROM", value_name = "ADDRESS")]
+    pub email_from: Option<String>,
+
+    /// Recipients of the summary email (comma separated or repeated)
+    #[arg(long, env = "EMAIL_TO", value_name = "ADDRESS", value_delimiter = ',')]
+    pub email_to: Vec<String>,
+
+    /// Telegram bot token used to announce new posts
+    #[arg(
+        long,
+        env = "TELEGRAM_TOKEN",
+        value_name = "TOKEN",
+        hide_env_values = true,
+        requires = "telegram_chat_id"
+    )]
+    pub telegram_token: Option<String>,
+
+    /// Chat (or @channel) that receives the Telegram announcement
+    #[arg(
+        long,
+        env = "TELEGRAM_CHAT_ID",
+        value_name = "CHAT",
+        requires = "telegram_token"
+    )]
+    pub telegram_chat_id: Option<String>,
+}
+
+/// What happened during one run, as reported to notification targets.
+#[derive(Serialize, Debug)]
+pub struct RunSummary<'a> {
+    pub blog: &'a str,
+    pub finished_at: String,
+    pub new_posts: Vec<PostSummary<'a>>,
+    pub changed_posts: &'a [String],
+    pub failed: usize,
+    pub interrupted: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostSummary<'a> {
+    pub title: String,
+    pub url: &'a str,
+    pub date: Option<NaiveDate>,
+}
+
+impl<'a> RunSummary<'a> {
+    pub fn new(
+        blog: &'a str,
+        new_posts: &'a [Post],
+        changed_posts: &'a [String],
+        failed: usize,
+        interrupted: bool,
+    ) -> Self {
+        RunSummary {
+            blog,
+            finished_at: Utc::now().to_rfc3339(),
+            new_posts: new_posts
+                .iter()
+                .map(|post| PostSummary {
+                    title: convert::html_to_text(&post.title),
+                    url: &post.URL,
+                    date: post.date,
+                })
+                .collect(),
+            changed_posts,
+            failed,
+            interrupted,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.new_posts.is_empty() && self.changed_posts.is_empty() && self.failed == 0
+    }
+}
+
+/// Sends the summary to every configured target. Runs with nothing to report
+/// are not announced, and a failed notification never fails the scrape.
+pub fn send_notifications(fetcher: &Fetcher, args: &NotifyArgs, summary: &RunSummary) {
+    if summary.is_empty() {
+        return;
+    }
+
+    if let Some(url) = &args.notify_webhook {
+        report("webhook", fetcher.post_json(url, summary));
+    }
+    if let Some(host) = &args.smtp_host {
+        if !args.email_to.is_empty() {
+            report("email", send_email(host, args, summary));
+        }
+    }
+    if let (Some(token), Some(chat_id)) = (&args.telegram_token, &args.telegram_chat_id) {
+        if !summary.new_posts.is_empty() {
+            report("telegram", send_telegram(fetcher, token, chat_id, summary));
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TelegramMessage<'a> {
+    chat_id: &'a str,
+    text: String,
+    disable_web_page_preview: bool,
+}
+
+fn send_telegram(
+    fetcher: &Fetcher,
+    token: &str,
+    chat_id: &str,
+    summary: &RunSummary,
+) -> Result<(), ScrapeError> {
+    let mut text = format!(
+        "{} new posts on {}\n",
+        summary.new_posts.len(),
+        summary.blog
+    );
+    for (i, post) in summary.new_posts.iter().enumerate() {
+        let line = format!("\n{}\n{}\n", post.title, post.url);
+        if text.len() + line.len() > TELEGRAM_MESSAGE_LIMIT {
+            text.push_str(&format!("\n…and {} more", summary.new_posts.len() - i));
+            break;
+        }
+        text.push_str(&line);
+    }
+
+    let message = TelegramMessage {
+        chat_id,
+        text,
+        disable_web_page_preview: summary.new_posts.len() > 1,
+    };
+    // The token is part of the URL, so keep it out of any error we log.
+    fetcher
+        .post_json(&format!("{TELEGRAM_API}/bot{token}/sendMessage"), &message)
+        .map_err(|e| match e {
+            ScrapeError::Network(e) => ScrapeError::Network(e.without_url()),
+            e => e,
+        })
+}
+
+fn send_email(host: &str, args: &NotifyArgs, summary: &RunSummary) -> Result<(), ScrapeError> {
+    let invalid = |e: &dyn std::fmt::Display| ScrapeError::Config(e.to_string());
+    let from: Mailbox = args
+        .email_from
+        .as_deref()
+        .ok_or_else(|| ScrapeError::Config("--email-from is required with --smtp-host".into()))?
+        .parse()
+        .map_err(|e| invalid(&e))?;
+
+    let mut builder = Message::builder().from(from).subject(format!(
+        "scrape_blogger: {} new posts on {}",
+        summary.new_posts.len(),
+        summary.blog
+    ));
+    for to in &args.email_to {
+        builder = builder.to(to.parse().map_err(|e| invalid(&e))?);
+    }
+    let message = builder
+        .body(render_text(summary))
+        .map_err(|e| invalid(&e))?;
+
+    let mut transport = SmtpTransport::starttls_relay(host)
+        .map_err(|e| ScrapeError::Notification(e.to_string()))?
+        .port(args.smtp_port);
+    if let (Some(user), Some(password)) = (&args.smtp_user, &args.smtp_password) {
+        transport = transport.credentials(Credentials::new(user.clone(), password.clone()));
+    }
+    transport
+        .build()
+        .send(&message)
+        .map_err(|e| ScrapeError::Notification(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Plain-text rendering of a summary for email and chat messages.
+fn render_text(summary: &RunSummary) -> String {
+    let mut text = format!(
+        "Scrape of {} finished at {}\n\nNew posts: {}\nChanged posts: {}\nFailed posts: {}\n",
+        summary.blog,
+        summary.finished_at,
+        summary.new_posts.len(),
+        summary.changed_posts.len(),
+        summary.failed
+    );
+    if summary.interrupted {
+        text.push_str("The run was interrupted before every post was scraped.\n");
+    }
+
+    if !summary.new_posts.is_empty() {
+        text.push_str("\nNew:\n");
+        for post in &summary.new_posts {
+            text.push_str(&format!("- {}\n  {}\n", post.title, post.url));
+        }
+    }
+    if !summary.changed_posts.is_empty() {
+        text.push_str("\nChanged:\n");
+        for url in summary.changed_posts {
+            text.push_str(&format!("- {}\n", url));
+        }
+    }
+
+    text
+}
+
+fn report(target: &str, result: Result<(), ScrapeError>) {
+    match result {
+        Ok(()) => info!(target, "notification sent"),
+        Err(e) => {
+            warn!(target, error = %e, "failed to send notification");
+            eprintln!("Failed to send {} notification: {}", target, e);
+        }
+    }
+}
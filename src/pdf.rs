@@ -0,0 +1,500 @@
+use super::convert;
+use super::export;
+use super::fetcher::Fetcher;
+use super::helpers;
+use crate::error::ScrapeError;
+use crate::Block as ContentBlock;
+use crate::Post;
+use printpdf::{
+    BuiltinFont, ColorBits, ColorSpace, Image, ImageFilter, ImageTransform, ImageXObject,
+    IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Px,
+};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const MAX_IMAGE_HEIGHT: f32 = 180.0;
+/// Pixels per inch assumed for images small enough to be shown at their natural size.
+const SCREEN_DPI: f32 = 96.0;
+const MM_PER_PT: f32 = 0.3528;
+
+const TITLE_SIZE: f32 = 18.0;
+const BODY_SIZE: f32 = 11.0;
+const META_SIZE: f32 = 9.0;
+
+/// The built-in PDF fonts carry no metrics we can query, so lines are wrapped
+/// by character count, tuned for Helvetica at each size on an A4 page.
+const TITLE_WRAP: usize = 48;
+const BODY_WRAP: usize = 88;
+const META_WRAP: usize = 105;
+const TOC_LINES_PER_PAGE: usize = 45;
+
+enum Item {
+    Text { text: String, size: f32, bold: bool },
+    Image { index: usize, dpi: f32 },
+    Gap,
+}
+
+/// An item with the vertical space it takes up, before pagination.
+struct Block {
+    height: f32,
+    item: Item,
+}
+
+/// An item positioned on a page; `y` is measured up from the bottom edge.
+struct Placed {
+    x: f32,
+    y: f32,
+    item: Item,
+}
+
+/// JPEG images fetched so far. Other formats would need decoding, which the
+/// PDF writer cannot do here, so they are listed by URL instead.
+#[derive(Default)]
+struct Images {
+    by_url: HashMap<String, Option<usize>>,
+    objects: Vec<ImageXObject>,
+}
+
+/// Renders the archive to PDF, oldest post first: a single volume with a
+/// table of contents and bookmarks, or with `per_post`, one file per post in
+/// the `output` directory.
+pub fn export_pdf(
+    fetcher: &Fetcher,
+    input: &Path,
+    output: &Path,
+    per_post: bool,
+) -> Result<(), ScrapeError> {
+    let posts = helpers::read_posts_from_file(input)?;
+    let mut ordered: Vec<&Post> = posts.iter().collect();
+    helpers::sort_backup_asc(&mut ordered)?;
+    println!(
+        "Building PDF from {} posts found in {}",
+        ordered.len(),
+        input.display()
+    );
+
+    let mut images = Images::default();
+    if per_post {
+        fs::create_dir_all(output)?;
+        for (post, slug) in ordered
+            .iter()
+            .zip(export::unique_slugs(ordered.iter().copied()))
+        {
+            let title = convert::html_to_text(&post.title);
+            let (pages, _) = paginate(vec![layout_post(fetcher, post, &mut images)]);
+            write_document(
+                &title,
+                pages,
+                &[],
+                &images,
+                &output.join(format!("{}.pdf", slug)),
+            )?;
+        }
+        println!(
+            "{} PDF files written to {}",
+            ordered.len(),
+            output.display()
+        );
+    } else {
+        let titles: Vec<String> = ordered
+            .iter()
+            .map(|post| convert::html_to_text(&post.title))
+            .collect();
+        let laid_out = ordered
+            .iter()
+            .map(|post| layout_post(fetcher, post, &mut images))
+            .collect();
+        let (content_pages, first_pages) = paginate(laid_out);
+
+        let toc_pages = titles.len().div_ceil(TOC_LINES_PER_PAGE);
+        let mut pages = table_of_contents(&titles, &first_pages, toc_pages);
+        pages.extend(content_pages);
+        let bookmarks: Vec<(String, usize)> = titles
+            .into_iter()
+            .zip(first_pages.iter().map(|page| page + toc_pages))
+            .collect();
+
+        write_document("Blog archive", pages, &bookmarks, &images, output)?;
+        println!(
+            "PDF with {} posts written to {}",
+            ordered.len(),
+            output.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn layout_post(fetcher: &Fetcher, post: &Post, images: &mut Images) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    push_text(
+        &mut blocks,
+        &convert::html_to_text(&post.title),
+        TITLE_SIZE,
+        true,
+    );
+    if let Some(date) = &post.date {
+        push_text(&mut blocks, &date.to_string(), META_SIZE, false);
+    }
+    push_text(&mut blocks, &post.URL, META_SIZE, false);
+    blocks.push(gap());
+
+    let content = helpers::post_blocks(post);
+    for content_block in content.iter() {
+        match content_block {
+            ContentBlock::Paragraph { text } => {
+                for line in text.lines() {
+                    push_text(&mut blocks, line, BODY_SIZE, false);
+                }
+            }
+            ContentBlock::Heading { text, .. } => push_text(&mut blocks, text, BODY_SIZE, true),
+            ContentBlock::List { ordered, items } => {
+                for (index, item) in items.iter().enumerate() {
+                    let marker = if *ordered {
+                        format!("{}.", index + 1)
+                    } else {
+                        "-".to_string()
+                    };
+                    push_text(
+                        &mut blocks,
+                        &format!("{} {}", marker, item),
+                        BODY_SIZE,
+                        false,
+                    );
+                }
+            }
+            ContentBlock::Image { src, caption, .. } => {
+                push_image(&mut blocks, fetcher, images, src);
+                if let Some(caption) = caption {
+                    push_text(&mut blocks, caption, META_SIZE, false);
+                }
+            }
+            ContentBlock::Blockquote { text } | ContentBlock::Code { text } => {
+                for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                    push_text(&mut blocks, line, META_SIZE, false);
+                }
+            }
+        }
+        blocks.push(gap());
+    }
+
+    // Images the body never placed, e.g. from posts archived without blocks
+    for url in &post.images {
+        let placed = content
+            .iter()
+            .any(|block| matches!(block, ContentBlock::Image { src, .. } if src == url));
+        if !placed {
+            push_image(&mut blocks, fetcher, images, url);
+            blocks.push(gap());
+        }
+    }
+
+    blocks
+}
+
+fn push_image(blocks: &mut Vec<Block>, fetcher: &Fetcher, images: &mut Images, url: &str) {
+    match images.get(fetcher, url) {
+        Some(index) => {
+            let image = &images.objects[index];
+            let (width, height) = (image.width.0 as f32, image.height.0 as f32);
+            // Shrink large images to fit the text column and page height,
+            // never enlarge small ones past their natural size.
+            let natural_mm = width * 25.4 / SCREEN_DPI;
+            let mut scale = (PAGE_WIDTH - 2.0 * MARGIN).min(natural_mm) / natural_mm;
+            scale = scale.min(MAX_IMAGE_HEIGHT / (height * 25.4 / SCREEN_DPI));
+            let dpi = SCREEN_DPI / scale;
+            blocks.push(Block {
+                height: height * 25.4 / dpi,
+                item: Item::Image { index, dpi },
+            });
+        }
+        None => push_text(blocks, &format!("[image: {}]", url), META_SIZE, false),
+    }
+}
+
+fn line_height(size: f32) -> f32 {
+    size * MM_PER_PT * 1.4
+}
+
+fn gap() -> Block {
+    Block {
+        height: line_height(BODY_SIZE) / 2.0,
+        item: Item::Gap,
+    }
+}
+
+fn push_text(blocks: &mut Vec<Block>, text: &str, size: f32, bold: bool) {
+    let width = match size {
+        s if s >= TITLE_SIZE => TITLE_WRAP,
+        s if s >= BODY_SIZE => BODY_WRAP,
+        _ => META_WRAP,
+    };
+    for line in wrap(text, width) {
+        blocks.push(Block {
+            height: line_height(size),
+            item: Item::Text {
+                text: line,
+                size,
+                bold,
+            },
+        });
+    }
+}
+
+/// Greedy word wrap by character count; words longer than a line are split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Flows each post onto fresh pages. Returns the pages and the index of the
+/// first page of every post.
+fn paginate(posts: Vec<Vec<Block>>) -> (Vec<Vec<Placed>>, Vec<usize>) {
+    let mut pages: Vec<Vec<Placed>> = Vec::new();
+    let mut first_pages = Vec::new();
+    for blocks in posts {
+        first_pages.push(pages.len());
+        pages.push(Vec::new());
+        let mut cursor = PAGE_HEIGHT - MARGIN;
+        for block in blocks {
+            let page_is_empty = pages.last().is_some_and(Vec::is_empty);
+            if cursor - block.height < MARGIN && !page_is_empty {
+                pages.push(Vec::new());
+                cursor = PAGE_HEIGHT - MARGIN;
+            }
+            let y = match &block.item {
+                Item::Text { size, .. } => cursor - size * MM_PER_PT,
+                _ => cursor - block.height,
+            };
+            cursor -= block.height;
+            if let Some(page) = pages.last_mut() {
+                page.push(Placed {
+                    x: MARGIN,
+                    y,
+                    item: block.item,
+                });
+            }
+        }
+    }
+    (pages, first_pages)
+}
+
+fn table_of_contents(
+    titles: &[String],
+    first_pages: &[usize],
+    toc_pages: usize,
+) -> Vec<Vec<Placed>> {
+    let row = line_height(BODY_SIZE);
+    let mut pages: Vec<Vec<Placed>> = Vec::new();
+    for (i, (title, first_page)) in titles.iter().zip(first_pages).enumerate() {
+        if i % TOC_LINES_PER_PAGE == 0 {
+            pages.push(vec![Placed {
+                x: MARGIN,
+                y: PAGE_HEIGHT - MARGIN - TITLE_SIZE * MM_PER_PT,
+                item: Item::Text {
+                    text: "Contents".to_string(),
+                    size: TITLE_SIZE,
+                    bold: true,
+                },
+            }]);
+        }
+        let y = PAGE_HEIGHT
+            - MARGIN
+            - line_height(TITLE_SIZE)
+            - row * (i % TOC_LINES_PER_PAGE + 1) as f32;
+        let mut lines = wrap(title, BODY_WRAP - 8).into_iter();
+        let mut title = lines.next().unwrap_or_default();
+        if lines.next().is_some() {
+            title.push('…');
+        }
+        if let Some(page) = pages.last_mut() {
+            page.push(Placed {
+                x: MARGIN,
+                y,
+                item: Item::Text {
+                    text: title,
+                    size: BODY_SIZE,
+                    bold: false,
+                },
+            });
+            page.push(Placed {
+                x: PAGE_WIDTH - MARGIN - 12.0,
+                y,
+                item: Item::Text {
+                    text: (first_page + toc_pages + 1).to_string(),
+                    size: BODY_SIZE,
+                    bold: false,
+                },
+            });
+        }
+    }
+    pages
+}
+
+fn write_document(
+    title: &str,
+    pages: Vec<Vec<Placed>>,
+    bookmarks: &[(String, usize)],
+    images: &Images,
+    output: &Path,
+) -> Result<(), ScrapeError> {
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let mut page_indexes = Vec::new();
+    for (number, placed) in pages.into_iter().enumerate() {
+        let (page, layer) = if number == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1")
+        };
+        page_indexes.push(page);
+        let layer = doc.get_page(page).get_layer(layer);
+        for item in placed {
+            draw(&layer, item, &regular, &bold, images);
+        }
+    }
+    for (name, page) in bookmarks {
+        if let Some(index) = page_indexes.get(*page) {
+            doc.add_bookmark(name.as_str(), *index);
+        }
+    }
+
+    save(doc, output)
+}
+
+fn draw(
+    layer: &PdfLayerReference,
+    placed: Placed,
+    regular: &IndirectFontRef,
+    bold: &IndirectFontRef,
+    images: &Images,
+) {
+    match placed.item {
+        Item::Text {
+            text,
+            size,
+            bold: is_bold,
+        } => {
+            let font = if is_bold { bold } else { regular };
+            layer.use_text(text, size, Mm(placed.x), Mm(placed.y), font);
+        }
+        Item::Image { index, dpi } => {
+            Image::from(images.objects[index].clone()).add_to_layer(
+                layer.clone(),
+                ImageTransform {
+                    translate_x: Some(Mm(placed.x)),
+                    translate_y: Some(Mm(placed.y)),
+                    dpi: Some(dpi),
+                    ..Default::default()
+                },
+            );
+        }
+        Item::Gap => {}
+    }
+}
+
+fn save(doc: PdfDocumentReference, output: &Path) -> Result<(), ScrapeError> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    doc.save(&mut writer)?;
+    Ok(())
+}
+
+impl Images {
+    fn get(&mut self, fetcher: &Fetcher, url: &str) -> Option<usize> {
+        if let Some(index) = self.by_url.get(url) {
+            return *index;
+        }
+
+        let index = match fetcher.fetch_bytes(url) {
+            Ok((bytes, _)) => jpeg_object(bytes).map(|object| {
+                self.objects.push(object);
+                self.objects.len() - 1
+            }),
+            Err(e) => {
+                eprintln!("Failed to download image {}: {}", url, e);
+                None
+            }
+        };
+        self.by_url.insert(url.to_string(), index);
+        index
+    }
+}
+
+/// Wraps JPEG bytes as a PDF image without decoding them; PDF readers
+/// decompress baseline and progressive JPEG natively.
+fn jpeg_object(bytes: Vec<u8>) -> Option<ImageXObject> {
+    let (width, height, components) = jpeg_dimensions(&bytes)?;
+    let color_space = match components {
+        1 => ColorSpace::Greyscale,
+        3 => ColorSpace::Rgb,
+        4 => ColorSpace::Cmyk,
+        _ => return None,
+    };
+    Some(ImageXObject {
+        width: Px(width),
+        height: Px(height),
+        color_space,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: bytes,
+        image_filter: Some(ImageFilter::DCT),
+        smask: None,
+        clipping_bbox: None,
+    })
+}
+
+/// Reads width, height and component count from the first start-of-frame
+/// segment of a JPEG file.
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(usize, usize, u8)> {
+    if bytes.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut i = 2;
+    while i + 9 < bytes.len() {
+        if bytes[i] != 0xFF {
+            return None;
+        }
+        let marker = bytes[i + 1];
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([bytes[i + 5], bytes[i + 6]]) as usize;
+            let width = u16::from_be_bytes([bytes[i + 7], bytes[i + 8]]) as usize;
+            return Some((width, height, bytes[i + 9]));
+        }
+        i += 2 + length;
+    }
+    None
+}
@@ -0,0 +1,121 @@
+use crate::error::ScrapeError;
+use regex::Regex;
+use scraper::Selector;
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// The selectors matching the template used by the default blog.
+pub static DEFAULT_PROFILE: LazyLock<SelectorProfile> = LazyLock::new(SelectorProfile::default);
+
+/// CSS selectors describing where a Blogger template puts each part of a
+/// post, and the rules for reading its title. Any field left out of a
+/// profile file keeps its default value.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SelectorProfile {
+    /// Container of the post list on listing pages; post links are read from inside it
+    pub post_list: String,
+    /// "Older Posts" pager link on listing pages
+    pub older_posts_link: String,
+    pub title: String,
+    /// Text removed from the start of titles, such as the blog name; the
+    /// first that matches is removed. A single string is also accepted
+    #[serde(alias = "title_prefix", deserialize_with = "one_or_many")]
+    pub title_prefixes: Vec<String>,
+    /// Regular expression finding a post's number in its title; the first
+    /// capture group is the id, or the whole match if there is none.
+    /// Compiled when the profile is read
+    #[serde(deserialize_with = "regex_pattern")]
+    pub id_pattern: Regex,
+    pub post_body: String,
+    pub date_header: String,
+    /// Name of the post's author, for blogs with more than one
+    pub author: String,
+    pub labels: String,
+    /// Element whose images are collected for a post
+    pub post_outer: String,
+}
+
+impl Default for SelectorProfile {
+    fn default() -> Self {
+        SelectorProfile {
+            post_list: "div.blog-posts.hfeed".to_string(),
+            older_posts_link: "a.blog-pager-older-link".to_string(),
+            title: "title".to_string(),
+            title_prefixes: vec!["Gnostic Esoteric Study &amp; Work Aids: ".to_string()],
+            id_pattern: Regex::new(r"\((\d+)\)$").unwrap(),
+            post_body: ".post-body.entry-content".to_string(),
+            date_header: ".date-header".to_string(),
+            author: ".post-author .fn, [itemprop=author] [itemprop=name]".to_string(),
+            labels: ".post-labels a".to_string(),
+            post_outer: ".post-outer".to_string(),
+        }
+    }
+}
+
+impl SelectorProfile {
+    /// Loads a profile from a `.toml` or `.json` file and checks that every
+    /// selector in it parses. The id pattern is checked while it is read.
+    pub fn load(path: &Path) -> Result<Self, ScrapeError> {
+        let raw = fs::read_to_string(path)?;
+        let profile: SelectorProfile = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&raw)?,
+            _ => toml::from_str(&raw).map_err(|e| ScrapeError::Config(e.to_string()))?,
+        };
+
+        for selector in [
+            &profile.post_list,
+            &profile.older_posts_link,
+            &profile.title,
+            &profile.post_body,
+            &profile.date_header,
+            &profile.author,
+            &profile.labels,
+            &profile.post_outer,
+        ] {
+            Selector::parse(selector)?;
+        }
+
+        Ok(profile)
+    }
+
+    /// Removes the first matching title prefix.
+    pub fn strip_title_prefix<'a>(&self, title: &'a str) -> &'a str {
+        self.title_prefixes
+            .iter()
+            .filter(|prefix| !prefix.is_empty())
+            .find_map(|prefix| title.trim_start().strip_prefix(prefix.as_str()))
+            .unwrap_or(title)
+    }
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(prefix) => vec![prefix],
+        OneOrMany::Many(prefixes) => prefixes,
+    })
+}
+
+fn regex_pattern<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(|e| serde::de::Error::custom(format!("invalid id_pattern: {}", e)))
+}
+
+/// Parses the `--profile` command line argument.
+pub fn parse_profile_arg(raw: &str) -> Result<SelectorProfile, String> {
+    SelectorProfile::load(Path::new(raw)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,186 @@
+use crate::error::ScrapeError;
+use crate::fetcher::Fetcher;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Counters shared by every worker thread during a scrape.
+pub struct RunStats {
+    started_at: DateTime<Utc>,
+    timer: Instant,
+    pub discovered: AtomicUsize,
+    pub previously_archived: AtomicUsize,
+    pub scraped: AtomicUsize,
+    pub updated: AtomicUsize,
+    pub not_modified: AtomicUsize,
+    pub skipped: AtomicUsize,
+    pub out_of_range: AtomicUsize,
+    pub retried: AtomicUsize,
+    pub failed: AtomicUsize,
+    /// Fewest requests allowed at once after the limit was lowered for errors
+    pub lowest_concurrency: AtomicUsize,
+    phases: Mutex<BTreeMap<String, f64>>,
+    /// Fetcher counters when the run started, since one fetcher may serve several runs
+    fetcher_baseline: (usize, usize, u64),
+}
+
+#[derive(Serialize, Debug)]
+pub struct RunReport {
+    pub started_at: String,
+    pub finished_at: String,
+    pub elapsed_secs: f64,
+    pub interrupted: bool,
+    pub output_file: String,
+    pub posts_in_output: usize,
+    pub discovered: usize,
+    pub previously_archived: usize,
+    pub scraped: usize,
+    pub updated: usize,
+    pub not_modified: usize,
+    pub skipped: usize,
+    pub out_of_range: usize,
+    pub retried: usize,
+    pub failed: usize,
+    pub lowest_concurrency: usize,
+    pub rate_limited: usize,
+    pub cache_hits: usize,
+    pub bytes_downloaded: u64,
+    pub phases_secs: BTreeMap<String, f64>,
+}
+
+impl RunStats {
+    pub fn new(fetcher: &Fetcher) -> Self {
+        RunStats {
+            started_at: Utc::now(),
+            timer: Instant::now(),
+            discovered: AtomicUsize::new(0),
+            previously_archived: AtomicUsize::new(0),
+            scraped: AtomicUsize::new(0),
+            updated: AtomicUsize::new(0),
+            not_modified: AtomicUsize::new(0),
+            skipped: AtomicUsize::new(0),
+            out_of_range: AtomicUsize::new(0),
+            retried: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            lowest_concurrency: AtomicUsize::new(0),
+            phases: Mutex::new(BTreeMap::new()),
+            fetcher_baseline: (
+                fetcher.rate_limited_count(),
+                fetcher.cache_hits(),
+                fetcher.bytes_downloaded(),
+            ),
+        }
+    }
+
+    pub fn count(counter: &AtomicUsize) -> usize {
+        counter.load(Ordering::SeqCst)
+    }
+
+    /// Adds the duration of a phase of the run, accumulating repeated phases.
+    pub fn record_phase(&self, phase: &str, duration: Duration) {
+        let mut phases = self.phases.lock().unwrap();
+        *phases.entry(phase.to_string()).or_insert(0.0) += duration.as_secs_f64();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+
+    pub fn report(
+        &self,
+        output_file: &str,
+        posts_in_output: usize,
+        interrupted: bool,
+        fetcher: &Fetcher,
+    ) -> RunReport {
+        RunReport {
+            started_at: self.started_at.to_rfc3339(),
+            finished_at: Utc::now().to_rfc3339(),
+            elapsed_secs: self.elapsed().as_secs_f64(),
+            interrupted,
+            output_file: output_file.to_string(),
+            posts_in_output,
+            discovered: Self::count(&self.discovered),
+            previously_archived: Self::count(&self.previously_archived),
+            scraped: Self::count(&self.scraped),
+            updated: Self::count(&self.updated),
+            not_modified: Self::count(&self.not_modified),
+            skipped: Self::count(&self.skipped),
+            out_of_range: Self::count(&self.out_of_range),
+            retried: Self::count(&self.retried),
+            failed: Self::count(&self.failed),
+            lowest_concurrency: Self::count(&self.lowest_concurrency),
+            rate_limited: fetcher.rate_limited_count() - self.fetcher_baseline.0,
+            cache_hits: fetcher.cache_hits() - self.fetcher_baseline.1,
+            bytes_downloaded: fetcher.bytes_downloaded() - self.fetcher_baseline.2,
+            phases_secs: self.phases.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Whether at least `interval` has passed since the run recorded in the report
+/// at `path` finished. A missing or unreadable report always counts as due.
+pub fn is_due(path: &Path, interval: Duration) -> bool {
+    let finished_at = fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).ok())
+        .and_then(|report| report["finished_at"].as_str().map(String::from))
+        .and_then(|raw| DateTime::parse_from_rfc3339(&raw).ok());
+
+    match finished_at {
+        Some(finished_at) => (Utc::now() - finished_at.with_timezone(&Utc))
+            .to_std()
+            .is_ok_and(|elapsed| elapsed >= interval),
+        None => true,
+    }
+}
+
+pub fn write_report(report: &RunReport, path: &Path) -> Result<(), ScrapeError> {
+    fs::write(path, serde_json::to_string_pretty(report)?)?;
+    println!("Run report written to {}", path.display());
+    Ok(())
+}
+
+/// A post that could not be scraped, kept so the next run can retry it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FailedUrl {
+    pub url: String,
+    pub error: String,
+}
+
+/// Writes the posts that failed during the run, replacing the list from any
+/// earlier run. A run without failures removes the file.
+pub fn write_failed_urls(failed: &[FailedUrl], path: &Path) -> Result<(), ScrapeError> {
+    if failed.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    let mut sorted = failed.to_vec();
+    sorted.sort_by(|a, b| a.url.cmp(&b.url));
+    fs::write(path, serde_json::to_string_pretty(&sorted)?)?;
+    println!(
+        "{} failed posts listed in {}; re-run with --retry-failures to try them again",
+        failed.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// URLs in a list written by `write_failed_urls`.
+pub fn read_failed_urls(path: &Path) -> Result<Vec<String>, ScrapeError> {
+    if !path.exists() {
+        return Err(ScrapeError::Config(format!(
+            "{} does not exist; the last run had no failures to retry",
+            path.display()
+        )));
+    }
+    let failed: Vec<FailedUrl> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(failed.into_iter().map(|failed| failed.url).collect())
+}
@@ -1,80 +1,86 @@
+use super::cache::PageCache;
+use super::convert;
+use super::fetcher::Fetcher;
 use super::helpers;
-use crate::Cli;
+use crate::attachments;
+use crate::dates;
+use crate::embeds;
+use crate::error::ScrapeError;
+use crate::links;
+use crate::profile::SelectorProfile;
+use crate::report::{self, FailedUrl, RunStats};
+use crate::BackupFormat;
+use crate::Comment;
+use crate::ContentArgs;
+use crate::Discovery;
 use crate::Post;
+use crate::ScrapeArgs;
+use chrono::{Datelike, NaiveDate, Utc};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use rayon::ThreadPool;
 use regex::Regex;
+use reqwest::Url;
 use scraper::{Html, Selector};
-use std::collections::HashSet;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
-const MAX_RETRIES: u32 = 4;
-const RETRY_DELAY: Duration = Duration::from_secs(1);
-const BACKUP_FILE_PATH: &str = "backup.json";
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Blogger launched in 1999, so no post can be older than this.
+const FIRST_BLOGGER_YEAR: i32 = 1999;
+const WAYBACK_AVAILABILITY_API: &str = "https://archive.org/wayback/available";
+
+/// Posts held after a scrape, plus the canonicalized URLs that were already
+/// archived when it started so callers can tell which posts are new.
+pub struct ScrapeOutcome {
+    pub posts: Vec<Post>,
+    pub previously_archived: HashSet<String>,
+    /// Archived posts whose title or text changed during this run
+    pub changed: Vec<String>,
+    /// Posts that could not be scraped, with the last error for each
+    pub failed: Vec<FailedUrl>,
+    /// Post links found during discovery
+    pub discovered: HashSet<String>,
+}
 
 pub fn search_and_scrape(
-    args: Cli,
-    error_written: Arc<Mutex<bool>>,
-    log_file: Arc<Mutex<File>>,
+    fetcher: &Fetcher,
+    pool: &ThreadPool,
+    args: ScrapeArgs,
+    stats: &RunStats,
+    interrupted: Arc<AtomicBool>,
     base_url: &str,
-) -> Result<Vec<Post>, Box<dyn std::error::Error>> {
-    let backup: Arc<Mutex<Vec<Post>>> = Arc::new(Mutex::new(Vec::new()));
-    match helpers::read_posts_from_file(Path::new(BACKUP_FILE_PATH)) {
-        Ok(file_backup) => {
-            if !file_backup.is_empty() {
-                println!(
-                    "{} was found and will be used to load previously archived posts",
-                    BACKUP_FILE_PATH
-                );
-                match backup.lock() {
-                    Ok(mut backup_guard) => {
-                        println!(
-                            "Successfully loaded {} posts from backup",
-                            &file_backup.len()
-                        );
-                        *backup_guard = file_backup;
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to acquire lock on backup: {}", e);
-                    }
-                }
-            } else {
-                println!(
-                    "{} was found but didn't contain any posts",
-                    BACKUP_FILE_PATH
-                );
-            }
-        }
-        Err(e) => {
-            if let Some(io_error) = e.downcast_ref::<std::io::Error>() {
-                match io_error.kind() {
-                    std::io::ErrorKind::NotFound => {
-                        println!("No backup file found matching {}", BACKUP_FILE_PATH);
-                    }
-                    std::io::ErrorKind::PermissionDenied => {
-                        eprintln!("Permission denied when trying to read {}", BACKUP_FILE_PATH);
-                    }
-                    _ => eprintln!("IO error reading backup file: {}", io_error),
-                }
-            } else if e.is::<serde_json::Error>() {
-                eprintln!("Failed to parse JSON in backup file: {}", e);
-            } else {
-                eprintln!("Unexpected error reading backup file: {}", e);
-            }
-        }
+) -> Result<ScrapeOutcome, ScrapeError> {
+    let backup = load_backup(&args.backup_path())?;
+    stats
+        .previously_archived
+        .store(backup.lock()?.len(), Ordering::SeqCst);
+    let archive_index = Mutex::new(helpers::archive_index(&backup.lock()?));
+    let archived_urls: HashSet<String> = archive_index.lock()?.keys().cloned().collect();
+    let validators_path = validators_path(&args.backup_path());
+    if let Err(e) = fetcher.load_validators(&validators_path) {
+        warn!(path = %validators_path.display(), error = %e, "failed to load cache validators");
     }
 
-    let post_links: HashSet<String> = if args.recent_only {
-        scrape_base_page_post_links(base_url)?
-    } else {
-        scrape_all_post_links(base_url, backup.clone())?
-    };
+    let discovery_timer = Instant::now();
+    let forced = forced_links(&args)?;
+    let post_links = discover_post_links(
+        fetcher,
+        pool,
+        &args,
+        base_url,
+        backup.clone(),
+        interrupted.clone(),
+        &forced,
+    )?;
+    stats.record_phase("discovery", discovery_timer.elapsed());
+    stats.discovered.store(post_links.len(), Ordering::SeqCst);
     println!(
         "{} posts were found and will now be scraped",
         post_links.len()
@@ -84,35 +90,72 @@ pub fn search_and_scrape(
     let pb = mp.add(ProgressBar::new(post_links.len() as u64));
     pb.set_style(
         ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")?
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")
+            .unwrap()
             .progress_chars("#>-"),
     );
     let progress = Arc::new(pb);
 
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(args.threads)
-        .build()
-        .unwrap();
+    let skipped: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    let changed: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    let failed: Mutex<Vec<FailedUrl>> = Mutex::new(Vec::new());
+    let scraping_timer = Instant::now();
 
     pool.install(|| {
         post_links.par_iter().for_each(|link| {
+            if interrupted.load(Ordering::SeqCst) {
+                stats.skipped.fetch_add(1, Ordering::SeqCst);
+                skipped.lock().unwrap().push(link.clone());
+                return;
+            }
+
             progress.set_message(format!("Scraping: {}", link));
 
-            match fetch_and_process_with_retries(link, log_file.clone()) {
-                Ok(post) => {
-                    let mut backup = backup.lock().unwrap();
-                    backup.push(post);
+            // A forced refresh must re-scrape even if the server says nothing changed
+            let conditional = archived_urls.contains(link) && !forced.contains(link);
+            match fetch_and_process_with_retries(fetcher, link, conditional, &args, stats)
+            {
+                Ok(None) => {
+                    stats.not_modified.fetch_add(1, Ordering::SeqCst);
+                    debug!(url = %link, "archived post not modified");
+                    touch_post(&backup, &archive_index, link);
+                }
+                Ok(Some(post)) if !post_in_range(&post, &args) => {
+                    stats.out_of_range.fetch_add(1, Ordering::SeqCst);
+                    debug!(url = %link, date = ?post.date, "post outside --since/--until, discarding");
+                }
+                Ok(Some(post)) => {
+                    debug!(url = %link, "scraped post");
+                    let title = post.title.clone();
+                    let new_hash = post.content_hash.clone();
+                    if let Some(previous) = upsert_post(&backup, &archive_index, post) {
+                        if Some(helpers::content_hash(&previous)) != new_hash {
+                            stats.updated.fetch_add(1, Ordering::SeqCst);
+                            changed.lock().unwrap().push(link.clone());
+                            info!(
+                                url = %link,
+                                title = %title,
+                                title_changed = previous.title != title,
+                                "archived post has changed"
+                            );
+                        }
+                    }
+                    let scraped = stats.scraped.fetch_add(1, Ordering::SeqCst) + 1;
+                    if args.checkpoint_every > 0 && scraped.is_multiple_of(args.checkpoint_every) {
+                        write_checkpoint(backup.clone(), &args.backup_path());
+                    }
                 }
                 Err(e) => {
-                    let mut err_written = error_written.lock().unwrap();
-                    *err_written = true;
-                    let mut log = log_file.lock().unwrap();
-                    writeln!(
-                        log,
-                        "[ERROR] Failed to scrape post: {} with error: {:?}",
-                        link, e
-                    )
-                    .ok();
+                    stats.failed.fetch_add(1, Ordering::SeqCst);
+                    if e.is_timeout() {
+                        error!(url = %link, error = %e, "failed to scrape post after repeated timeouts");
+                    } else {
+                        error!(url = %link, error = %e, "failed to scrape post");
+                    }
+                    failed.lock().unwrap().push(FailedUrl {
+                        url: link.clone(),
+                        error: e.to_string(),
+                    });
                 }
             }
 
@@ -120,25 +163,360 @@ pub fn search_and_scrape(
         });
     });
 
-    progress.finish_with_message("All posts processed!");
+    stats.record_phase("scraping", scraping_timer.elapsed());
+    if let Some(lowest) = fetcher.lowest_request_limit() {
+        stats.lowest_concurrency.store(lowest, Ordering::SeqCst);
+    }
+    if let Err(e) = fetcher.save_validators(&validators_path) {
+        warn!(path = %validators_path.display(), error = %e, "failed to save cache validators");
+    }
+    if args.check_updates {
+        println!(
+            "{} archived posts changed since they were last scraped, see log for details",
+            RunStats::count(&stats.updated)
+        );
+    }
+
+    let skipped = skipped.into_inner()?;
+    if skipped.is_empty() {
+        progress.finish_with_message("All posts processed!");
+    } else {
+        progress.abandon_with_message(format!(
+            "Interrupted with {} of {} posts processed",
+            post_links.len() - skipped.len(),
+            post_links.len()
+        ));
+        warn!(
+            processed = post_links.len() - skipped.len(),
+            total = post_links.len(),
+            "run interrupted before all posts were scraped"
+        );
+        for link in skipped {
+            warn!(url = %link, "post not scraped before interrupt");
+        }
+    }
 
     let backup = Arc::try_unwrap(backup).unwrap().into_inner()?;
 
+    Ok(ScrapeOutcome {
+        posts: helpers::dedupe_canonical(backup),
+        previously_archived: archived_urls,
+        changed: changed.into_inner()?,
+        failed: failed.into_inner()?,
+        discovered: post_links,
+    })
+}
+
+/// Replaces the archived entry for the same post, matched on canonical URL so
+/// a copy archived under an older form of its address is replaced too, or
+/// appends a new one. Returns the entry that was replaced. An unchanged post
+/// keeps its original `last_modified` time, and an id entered with assign-ids
+/// is kept unless the title now carries one.
+fn upsert_post(
+    backup: &Mutex<Vec<Post>>,
+    archive_index: &Mutex<HashMap<String, usize>>,
+    mut post: Post,
+) -> Option<Post> {
+    let mut backup = backup.lock().unwrap();
+    let mut archive_index = archive_index.lock().unwrap();
+    let keys = [
+        helpers::canonicalize_post_url(&post.URL),
+        helpers::canonical_key(&post),
+    ];
+    match keys.iter().find_map(|key| archive_index.get(key).copied()) {
+        Some(position) => {
+            let archived = &mut backup[position];
+            if post.content_hash == Some(helpers::content_hash(archived)) {
+                post.last_modified = archived.last_modified.clone();
+            }
+            if post.id.is_none() && archived.id_assigned_manually {
+                post.id = archived.id.clone();
+                post.id_assigned_manually = true;
+            }
+            for key in keys {
+                archive_index.insert(key, position);
+            }
+            Some(std::mem::replace(archived, post))
+        }
+        None => {
+            for key in keys {
+                archive_index.insert(key, backup.len());
+            }
+            backup.push(post);
+            None
+        }
+    }
+}
+
+/// Records that an archived post was checked and found unchanged.
+fn touch_post(backup: &Mutex<Vec<Post>>, archive_index: &Mutex<HashMap<String, usize>>, url: &str) {
+    let position = archive_index
+        .lock()
+        .unwrap()
+        .get(&helpers::canonicalize_post_url(url))
+        .copied();
+    if let Some(position) = position {
+        backup.lock().unwrap()[position].last_checked = Some(Utc::now().to_rfc3339());
+    }
+}
+
+/// Loads previously archived posts from the backup file and any checkpoint
+/// left behind by an interrupted run. Only a missing backup starts an empty
+/// archive; any other failure is returned so the run stops before it could
+/// write over the existing backup.
+fn load_backup(path: &Path) -> Result<Arc<Mutex<Vec<Post>>>, ScrapeError> {
+    let file_backup = match helpers::read_posts_from_file(path) {
+        Ok(file_backup) => file_backup,
+        Err(ScrapeError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("No backup file found matching {}", path.display());
+            Vec::new()
+        }
+        Err(e) => {
+            return Err(ScrapeError::Backup {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+        }
+    };
+    if path.exists() {
+        if file_backup.is_empty() {
+            println!("{} was found but didn't contain any posts", path.display());
+        } else {
+            println!(
+                "{} was found and will be used to load previously archived posts",
+                path.display()
+            );
+            println!(
+                "Successfully loaded {} posts from backup",
+                file_backup.len()
+            );
+        }
+    }
+
+    let backup = Arc::new(Mutex::new(file_backup));
+    resume_from_checkpoint(backup.clone(), path);
     Ok(backup)
 }
 
-pub fn extract_post_links(document: &Html) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
-    let div_selector = Selector::parse("div.blog-posts.hfeed").unwrap();
+fn discover_post_links(
+    fetcher: &Fetcher,
+    pool: &ThreadPool,
+    args: &ScrapeArgs,
+    base_url: &str,
+    backup: Arc<Mutex<Vec<Post>>>,
+    interrupted: Arc<AtomicBool>,
+    forced: &HashSet<String>,
+) -> Result<HashSet<String>, ScrapeError> {
+    let archived: Vec<String> = if args.check_updates {
+        backup.lock()?.iter().map(|post| post.URL.clone()).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut links = if args.retry_failures {
+        let links: HashSet<String> = report::read_failed_urls(&args.failed_urls)?
+            .iter()
+            .map(|url| helpers::canonicalize_post_url(url))
+            .collect();
+        println!(
+            "{} posts that failed last time will be retried",
+            links.len()
+        );
+        links
+    } else if !args.refresh.is_empty() || args.refresh_from.is_some() {
+        forced.clone()
+    } else if args.recent_only {
+        scrape_base_page_post_links(fetcher, base_url, args.content.selectors())?
+    } else if args.discovery != Discovery::Pager && args.label.is_some() {
+        return Err(ScrapeError::Config(
+            "--label can only be used with pager discovery".to_string(),
+        ));
+    } else if args.discovery == Discovery::Sitemap {
+        scrape_sitemap_post_links(fetcher, base_url, backup)?
+    } else if args.discovery == Discovery::Windows {
+        scrape_windowed_post_links(fetcher, pool, args, base_url, backup, interrupted)?
+    } else {
+        // A label's listing is crawled in full so the label file can include
+        // archived posts, whatever labels they were stored with
+        let archive = if args.label.is_some() {
+            Arc::new(Mutex::new(Vec::new()))
+        } else {
+            backup
+        };
+        scrape_all_post_links(
+            fetcher,
+            base_url,
+            archive,
+            interrupted,
+            args.since,
+            args.content.selectors(),
+        )?
+    };
+    links.extend(archived);
+
+    Ok(links
+        .into_iter()
+        .filter(|link| link_in_range(link, args))
+        .collect())
+}
+
+/// Collects the URLs given through --refresh and --refresh-from, which are
+/// scraped again without a conditional request.
+fn forced_links(args: &ScrapeArgs) -> Result<HashSet<String>, ScrapeError> {
+    if args.refresh.is_empty() && args.refresh_from.is_none() {
+        return Ok(HashSet::new());
+    }
+    let mut links: HashSet<String> = args
+        .refresh
+        .iter()
+        .map(|url| helpers::canonicalize_post_url(url))
+        .collect();
+    if let Some(path) = &args.refresh_from {
+        links.extend(
+            helpers::read_url_list(path)?
+                .iter()
+                .map(|url| helpers::canonicalize_post_url(url)),
+        );
+    }
+    println!("{} posts will be refreshed", links.len());
+    Ok(links)
+}
+
+/// Checks the `/YYYY/MM/` segment of a post URL against --since/--until.
+/// Links without one are kept and checked again once the post is scraped.
+fn link_in_range(link: &str, args: &ScrapeArgs) -> bool {
+    let Some(month) = helpers::url_year_month(link) else {
+        return true;
+    };
+    args.since
+        .is_none_or(|since| month >= (since.year(), since.month()))
+        && args
+            .until
+            .is_none_or(|until| month <= (until.year(), until.month()))
+}
+
+fn post_in_range(post: &Post, args: &ScrapeArgs) -> bool {
+    let Some(date) = post.date else {
+        return true;
+    };
+    args.since.is_none_or(|since| date >= since) && args.until.is_none_or(|until| date <= until)
+}
+
+/// Runs link discovery only and reports what a real run would fetch.
+pub fn dry_run(
+    fetcher: &Fetcher,
+    pool: &ThreadPool,
+    args: &ScrapeArgs,
+    interrupted: Arc<AtomicBool>,
+    base_url: &str,
+) -> Result<(), ScrapeError> {
+    let backup = load_backup(&args.backup_path())?;
+    let archived = backup.lock()?.len();
+    let archived_urls: HashSet<String> = helpers::archive_index(&backup.lock()?)
+        .into_keys()
+        .collect();
+    let forced = forced_links(args)?;
+    let mut post_links: Vec<String> =
+        discover_post_links(fetcher, pool, args, base_url, backup, interrupted, &forced)?
+            .into_iter()
+            .collect();
+    post_links.sort();
+
+    let mut rechecked = 0;
+    for link in &post_links {
+        if archived_urls.contains(link) {
+            rechecked += 1;
+            info!(url = %link, "would re-check archived post");
+        } else {
+            info!(url = %link, "would scrape post");
+        }
+    }
+    println!(
+        "Dry run: {} new posts would be scraped and {} archived posts re-checked ({} already archived). Nothing was written.",
+        post_links.len() - rechecked,
+        rechecked,
+        archived
+    );
+
+    Ok(())
+}
+
+/// Merges posts saved by an earlier run that crashed or was killed before it
+/// could write the backup file.
+fn resume_from_checkpoint(backup: Arc<Mutex<Vec<Post>>>, backup_path: &Path) {
+    let checkpoint_path = checkpoint_path(backup_path);
+    let checkpoint = match helpers::read_posts_from_file(&checkpoint_path) {
+        Ok(posts) => posts,
+        Err(_) => return,
+    };
+
+    let mut backup = backup.lock().unwrap();
+    let archived: HashSet<String> = backup.iter().map(|post| post.URL.clone()).collect();
+    let recovered: Vec<Post> = checkpoint
+        .into_iter()
+        .filter(|post| !archived.contains(&post.URL))
+        .collect();
+
+    println!(
+        "{} was found and {} unsaved posts were recovered from it",
+        checkpoint_path.display(),
+        recovered.len()
+    );
+    backup.extend(recovered);
+}
+
+fn write_checkpoint(backup: Arc<Mutex<Vec<Post>>>, backup_path: &Path) {
+    let checkpoint_path = checkpoint_path(backup_path);
+    let snapshot = backup.lock().unwrap().clone();
+    match helpers::write_posts(&snapshot, &checkpoint_path, BackupFormat::Json) {
+        Ok(()) => debug!(posts = snapshot.len(), "checkpoint written"),
+        Err(e) => {
+            warn!(path = %checkpoint_path.display(), error = %e, "failed to write checkpoint")
+        }
+    }
+}
+
+/// Removes the checkpoint once its contents have been written to the real output.
+pub fn clear_checkpoint(backup_path: &Path) {
+    let checkpoint_path = checkpoint_path(backup_path);
+    if let Err(e) = fs::remove_file(&checkpoint_path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("Failed to remove {}: {}", checkpoint_path.display(), e);
+        }
+    }
+}
+
+/// In-progress results for `backup.json` are kept in `backup.json.checkpoint`.
+fn checkpoint_path(backup_path: &Path) -> PathBuf {
+    let mut path = backup_path.as_os_str().to_owned();
+    path.push(".checkpoint");
+    PathBuf::from(path)
+}
+
+/// Cache validators for `backup.json` are kept in `backup.validators.json`,
+/// so every archive only replays validators from its own pages.
+fn validators_path(backup_path: &Path) -> PathBuf {
+    let stem = backup_path
+        .file_stem()
+        .map_or_else(|| "backup".into(), |stem| stem.to_string_lossy());
+    backup_path.with_file_name(format!("{}.validators.json", stem))
+}
+
+pub fn extract_post_links(
+    document: &Html,
+    base_url: &str,
+    selectors: &SelectorProfile,
+) -> Result<HashSet<String>, ScrapeError> {
+    let div_selector = Selector::parse(&selectors.post_list)?;
     let a_selector = Selector::parse("a").unwrap();
-    let regex =
-        Regex::new(r"^https://gnosticesotericstudyworkaids\.blogspot\.com/\d+/.*\.html$").unwrap();
+    let regex = helpers::post_link_regex(base_url)?;
 
     if let Some(div) = document.select(&div_selector).next() {
         let hrefs = div
             .select(&a_selector)
             .filter_map(|a| a.value().attr("href"))
+            .map(helpers::canonicalize_post_url)
             .filter(|href| regex.is_match(href))
-            .map(String::from)
             .collect::<HashSet<_>>();
 
         return Ok(hrefs);
@@ -147,18 +525,211 @@ pub fn extract_post_links(document: &Html) -> Result<HashSet<String>, Box<dyn st
     Ok(HashSet::new())
 }
 
+pub fn extract_comments(document: &Html) -> Result<Vec<Comment>, ScrapeError> {
+    let thread_comment_selector = Selector::parse(".comment-thread li.comment")?;
+    let thread_author_selector = Selector::parse(".comment-block .user")?;
+    let thread_date_selector = Selector::parse(".comment-block .datetime")?;
+    let thread_body_selector = Selector::parse(".comment-block .comment-content")?;
+
+    let collect_text = |element: scraper::ElementRef| {
+        element
+            .text()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut comments = Vec::new();
+    for comment in document.select(&thread_comment_selector) {
+        let body = match comment.select(&thread_body_selector).next() {
+            Some(body) => collect_text(body),
+            None => continue,
+        };
+        let author = comment
+            .select(&thread_author_selector)
+            .next()
+            .map(collect_text)
+            .unwrap_or_else(|| "Anonymous".to_string());
+        let date = comment
+            .select(&thread_date_selector)
+            .next()
+            .map(collect_text);
+        comments.push(Comment { author, date, body });
+    }
+
+    if !comments.is_empty() {
+        return Ok(comments);
+    }
+
+    // Older templates render comments as a definition list instead of threads
+    let legacy_block_selector = Selector::parse("dl#comments-block")?;
+    let legacy_author_selector = Selector::parse("dt.comment-author")?;
+    let legacy_body_selector = Selector::parse("dd.comment-body")?;
+    let legacy_footer_selector = Selector::parse("dd.comment-footer")?;
+
+    if let Some(block) = document.select(&legacy_block_selector).next() {
+        let authors = block.select(&legacy_author_selector);
+        let bodies = block.select(&legacy_body_selector);
+        let footers = block.select(&legacy_footer_selector);
+
+        for ((author, body), footer) in authors.zip(bodies).zip(footers) {
+            let author = collect_text(author)
+                .trim_end_matches("said...")
+                .trim()
+                .to_string();
+            comments.push(Comment {
+                author,
+                date: Some(collect_text(footer)).filter(|d| !d.is_empty()),
+                body: collect_text(body),
+            });
+        }
+    }
+
+    Ok(comments)
+}
+
+/// Collects post links from `/sitemap.xml`, following the sub-sitemaps that
+/// Blogger splits larger blogs into.
+pub fn scrape_sitemap_post_links(
+    fetcher: &Fetcher,
+    base_url: &str,
+    backup: Arc<Mutex<Vec<Post>>>,
+) -> Result<HashSet<String>, ScrapeError> {
+    let archived_links: HashSet<String> =
+        backup.lock()?.iter().map(|post| post.URL.clone()).collect();
+    let loc_regex = Regex::new(r"<loc>\s*([^<]+?)\s*</loc>").unwrap();
+    let post_regex = helpers::post_link_regex(base_url)?;
+
+    let mut pending = vec![Url::parse(base_url)
+        .and_then(|url| url.join("/sitemap.xml"))
+        .map_err(|e| ScrapeError::Config(e.to_string()))?
+        .to_string()];
+    let mut visited = HashSet::new();
+    let mut links = HashSet::new();
+
+    while let Some(sitemap_url) = pending.pop() {
+        if !visited.insert(sitemap_url.clone()) {
+            continue;
+        }
+        debug!(url = %sitemap_url, "reading sitemap");
+        let xml = fetcher.fetch_html(&sitemap_url)?;
+        let is_index = xml.contains("<sitemapindex");
+        for cap in loc_regex.captures_iter(&xml) {
+            let loc = cap[1].replace("&amp;", "&");
+            if is_index {
+                pending.push(loc);
+                continue;
+            }
+            let loc = helpers::canonicalize_post_url(&loc);
+            if post_regex.is_match(&loc) && !archived_links.contains(&loc) {
+                links.insert(loc);
+            }
+        }
+    }
+
+    println!(
+        "Read {} sitemaps and found {} new posts",
+        visited.len(),
+        links.len()
+    );
+    Ok(links)
+}
+
+/// Splits the archive into one `updated-min`/`updated-max` listing per year
+/// and follows each listing's pager on its own worker thread.
+pub fn scrape_windowed_post_links(
+    fetcher: &Fetcher,
+    pool: &ThreadPool,
+    args: &ScrapeArgs,
+    base_url: &str,
+    backup: Arc<Mutex<Vec<Post>>>,
+    interrupted: Arc<AtomicBool>,
+) -> Result<HashSet<String>, ScrapeError> {
+    let archived_links: HashSet<String> =
+        backup.lock()?.iter().map(|post| post.URL.clone()).collect();
+    let first_year = args.since.map_or(FIRST_BLOGGER_YEAR, |since| since.year());
+    let last_year = args.until.map_or(Utc::now().year(), |until| until.year());
+    let years: Vec<i32> = (first_year..=last_year).rev().collect();
+
+    let pages_read = AtomicUsize::new(0);
+
+    let windows: Vec<HashSet<String>> = pool.install(|| {
+        years
+            .par_iter()
+            .map(|year| {
+                let window_url = format!(
+                    "{}search?updated-min={}-01-01T00:00:00%2B00:00&updated-max={}-01-01T00:00:00%2B00:00&max-results=50",
+                    base_url,
+                    year,
+                    year + 1
+                );
+                let mut links = HashSet::new();
+                let mut visited = HashSet::new();
+                let mut current_url = Some(window_url);
+
+                while let Some(url) = current_url.take() {
+                    if interrupted.load(Ordering::SeqCst) || !visited.insert(url.clone()) {
+                        break;
+                    }
+                    let document = Html::parse_document(&fetcher.fetch_html(&url)?);
+                    pages_read.fetch_add(1, Ordering::SeqCst);
+
+                    let page_links = extract_post_links(&document, base_url, args.content.selectors())?;
+                    // The pager can run past the window, so stop at the first
+                    // page without posts from this year
+                    let in_window: Vec<String> = page_links
+                        .into_iter()
+                        .filter(|link| {
+                            helpers::url_year_month(link).is_none_or(|(y, _)| y == *year)
+                        })
+                        .collect();
+                    if in_window.is_empty() {
+                        break;
+                    }
+                    links.extend(
+                        in_window
+                            .into_iter()
+                            .filter(|link| !archived_links.contains(link)),
+                    );
+                    current_url =
+                        helpers::find_older_posts_link(&document, args.content.selectors());
+                }
+
+                debug!(year, posts = links.len(), "finished discovery window");
+                Ok(links)
+            })
+            .collect::<Result<Vec<_>, ScrapeError>>()
+    })?;
+
+    let links: HashSet<String> = windows.into_iter().flatten().collect();
+    println!(
+        "Read {} listing pages across {} yearly windows and found {} new posts",
+        pages_read.load(Ordering::SeqCst),
+        years.len(),
+        links.len()
+    );
+    Ok(links)
+}
+
 pub fn scrape_base_page_post_links(
+    fetcher: &Fetcher,
     base_url: &str,
-) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
-    let html = helpers::fetch_html(base_url)?;
+    selectors: &SelectorProfile,
+) -> Result<HashSet<String>, ScrapeError> {
+    let html = fetcher.fetch_html(base_url)?;
     let document = Html::parse_document(&html);
-    extract_post_links(&document)
+    extract_post_links(&document, base_url, selectors)
 }
 
 pub fn scrape_all_post_links(
+    fetcher: &Fetcher,
     base_url: &str,
     backup: Arc<Mutex<Vec<Post>>>,
-) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    interrupted: Arc<AtomicBool>,
+    since: Option<NaiveDate>,
+    selectors: &SelectorProfile,
+) -> Result<HashSet<String>, ScrapeError> {
     let archived_links: HashSet<String> = match backup.lock() {
         Ok(backup_handle) => backup_handle.iter().map(|post| post.URL.clone()).collect(),
         Err(e) => {
@@ -185,10 +756,20 @@ pub fn scrape_all_post_links(
     );
 
     loop {
-        let html = helpers::fetch_html(&current_url)?;
+        let html = fetcher.fetch_html(&current_url)?;
         let document = Html::parse_document(&html);
 
-        let new_links: HashSet<String> = extract_post_links(&document)?
+        let page_links = extract_post_links(&document, base_url, selectors)?;
+        // Pages run newest to oldest, so once a whole page predates --since
+        // no later page can contain a wanted post.
+        let past_since = since.is_some_and(|since| {
+            !page_links.is_empty()
+                && page_links.iter().all(|link| {
+                    helpers::url_year_month(link)
+                        .is_some_and(|month| month < (since.year(), since.month()))
+                })
+        });
+        let new_links: HashSet<String> = page_links
             .into_iter()
             .filter(|link| !archived_links.contains(link))
             .collect();
@@ -206,7 +787,17 @@ pub fn scrape_all_post_links(
 
         button_count += 1;
 
-        if let Some(next_url) = helpers::find_older_posts_link(&document) {
+        if interrupted.load(Ordering::SeqCst) {
+            println!("Link discovery interrupted after {} pages", button_count);
+            break;
+        }
+
+        if let Some(since) = since.filter(|_| past_since) {
+            println!("Reached posts older than {}, stopping discovery", since);
+            break;
+        }
+
+        if let Some(next_url) = helpers::find_older_posts_link(&document, selectors) {
             if visited_urls.contains(&next_url) {
                 println!("Pagination loop detected: {}", next_url);
                 break;
@@ -228,73 +819,240 @@ pub fn scrape_all_post_links(
 }
 
 pub fn fetch_and_process_with_retries(
+    fetcher: &Fetcher,
     url: &str,
-    logfile: Arc<Mutex<File>>,
-) -> Result<Post, Box<dyn std::error::Error>> {
+    conditional: bool,
+    args: &ScrapeArgs,
+    stats: &RunStats,
+) -> Result<Option<Post>, ScrapeError> {
     let mut attempts = 0;
 
     loop {
         attempts += 1;
 
-        match fetch_and_process_post(url) {
+        let result = fetch_and_process_post(fetcher, url, conditional, args);
+        // Only this attempt's own outcome counts; pages that are gone or
+        // unparseable say nothing about server load
+        fetcher.record_attempt(result.as_ref().is_err_and(ScrapeError::is_retryable));
+        match result {
             Ok(post) => {
                 return Ok(post);
             }
             Err(e) => {
-                if attempts >= MAX_RETRIES {
+                if attempts >= args.max_retries || !e.is_retryable() {
                     return Err(e);
                 } else {
-                    let mut log = logfile.lock().unwrap();
-                    writeln!(
-                        log,
-                        "[WARN] Failed to scrape post: {} on attempt {}/{}. Retrying after delay...",
-                        url, attempts, MAX_RETRIES
-                    )
-                    .ok();
-                    thread::sleep(RETRY_DELAY);
+                    let delay =
+                        backoff_delay(Duration::from_millis(args.retry_base_delay), attempts);
+                    warn!(
+                        url,
+                        attempt = attempts,
+                        max_retries = args.max_retries,
+                        delay_ms = delay.as_millis() as u64,
+                        timed_out = e.is_timeout(),
+                        error = %e,
+                        "failed to scrape post, retrying after delay"
+                    );
+                    stats.retried.fetch_add(1, Ordering::SeqCst);
+                    thread::sleep(delay);
                 }
             }
         }
     }
 }
 
-fn fetch_and_process_post(url: &str) -> Result<Post, Box<dyn std::error::Error>> {
-    let html = helpers::fetch_html(url)?;
-    let document = Html::parse_document(&html);
+/// Doubles the base delay for every failed attempt, then keeps half of it and
+/// randomizes the rest so threads that failed together don't retry in lockstep.
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let exponential = base.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+    let capped = exponential.min(MAX_RETRY_DELAY);
+    let half = capped / 2;
+    half + half.mul_f64(rand::random::<f64>())
+}
 
-    let title_selector = Selector::parse("title")?;
-    let date_header_selector = Selector::parse(".date-header")?;
-    let post_body_selector = Selector::parse(".post-body.entry-content")?;
+/// Scrapes a single post. With `conditional` set the request carries the
+/// page's cache validators and `None` is returned if it has not changed.
+fn fetch_and_process_post(
+    fetcher: &Fetcher,
+    url: &str,
+    conditional: bool,
+    args: &ScrapeArgs,
+) -> Result<Option<Post>, ScrapeError> {
+    let fetched = if conditional {
+        fetcher.fetch_html_if_modified(url)
+    } else {
+        fetcher.fetch_html_cached(url).map(Some)
+    };
+    let html = match fetched {
+        Ok(Some(html)) => html,
+        Ok(None) => return Ok(None),
+        Err(ScrapeError::Gone { status, .. }) if args.wayback_fallback => {
+            warn!(url, status = %status, "post is gone, falling back to the Wayback Machine");
+            return fetch_from_wayback(fetcher, url, &args.content).map(Some);
+        }
+        Err(e) => return Err(e),
+    };
+    fetcher.save_snapshot(url, &html);
+
+    let mut post = parse_post(url, &html, &args.content)?;
+    if args.embed_thumbnails {
+        embeds::resolve_thumbnails(fetcher, &mut post.embeds);
+    }
+    if let Some(dir) = &args.download_attachments {
+        attachments::download_attachments(fetcher, &mut post.attachments, dir);
+    }
+    if post.canonical_url.is_none() {
+        post.canonical_url = fetcher
+            .final_url(url)
+            .map(|final_url| helpers::canonicalize_post_url(&final_url));
+    }
+    Ok(Some(post))
+}
+
+/// Scrapes the most recent Internet Archive capture of a post.
+fn fetch_from_wayback(
+    fetcher: &Fetcher,
+    url: &str,
+    content: &ContentArgs,
+) -> Result<Post, ScrapeError> {
+    let gone = || ScrapeError::Gone {
+        url: url.to_string(),
+        status: reqwest::StatusCode::NOT_FOUND,
+    };
+    let api_url = format!("{}?url={}", WAYBACK_AVAILABILITY_API, url);
+    let availability: serde_json::Value = serde_json::from_str(&fetcher.fetch_html(&api_url)?)?;
+    let closest = &availability["archived_snapshots"]["closest"];
+    if closest["available"].as_bool() != Some(true) {
+        return Err(gone());
+    }
+    let (Some(timestamp), Some((_, original))) = (
+        closest["timestamp"].as_str(),
+        closest["url"]
+            .as_str()
+            .and_then(|snapshot| snapshot.split_once("/http")),
+    ) else {
+        return Err(gone());
+    };
+
+    // The id_ modifier returns the page as captured, without the Wayback toolbar
+    let snapshot_url = format!(
+        "https://web.archive.org/web/{}id_/http{}",
+        timestamp, original
+    );
+    debug!(url, snapshot = %snapshot_url, "fetching Wayback snapshot");
+    let html = fetcher.fetch_html(&snapshot_url)?;
+
+    let mut post = parse_post(url, &html, content)?;
+    post.source = Some("wayback".to_string());
+    Ok(post)
+}
+
+/// Rebuilds posts from the snapshots saved by an earlier scrape.
+pub fn reparse_snapshots(dir: &Path, content: &ContentArgs) -> Result<Vec<Post>, ScrapeError> {
+    let snapshots = PageCache::new(dir.to_path_buf())?.entries()?;
+    println!(
+        "Reparsing {} snapshots from {}",
+        snapshots.len(),
+        dir.display()
+    );
+
+    let mut posts = Vec::new();
+    for (url, fetched_at, html) in snapshots {
+        match parse_post(&url, &html, content) {
+            Ok(mut post) => {
+                post.last_checked = Some(fetched_at.clone());
+                post.last_modified = Some(fetched_at);
+                posts.push(post);
+            }
+            Err(e) => error!(url = %url, error = %e, "failed to reparse snapshot"),
+        }
+    }
+    println!("{} posts were rebuilt", posts.len());
+
+    Ok(posts)
+}
+
+/// Extracts a post from the HTML of its page.
+fn parse_post(url: &str, html: &str, options: &ContentArgs) -> Result<Post, ScrapeError> {
+    let document = Html::parse_document(html);
+
+    let selectors = options.selectors();
+    let title_selector = Selector::parse(&selectors.title)?;
+    let date_header_selector = Selector::parse(&selectors.date_header)?;
+    let author_selector = Selector::parse(&selectors.author)?;
+    let post_body_selector = Selector::parse(&selectors.post_body)?;
+    let labels_selector = Selector::parse(&selectors.labels)?;
 
     let title = document
         .select(&title_selector)
         .next()
-        .ok_or("Title not found")?
-        .inner_html()
-        .replace("Gnostic Esoteric Study &amp; Work Aids: ", "");
-
-    let id = helpers::extract_id_from_title(&title);
-
-    let content = document
-        .select(&post_body_selector)
-        .filter_map(|element| {
-            let text = element.text().collect::<Vec<_>>().join(" ");
-            if !text.is_empty() {
-                Some(text)
-            } else {
-                None
-            }
-        })
+        .ok_or_else(|| ScrapeError::parse(url, "title not found"))?
+        .inner_html();
+    let title = selectors.strip_title_prefix(&title).to_string();
+
+    let id = helpers::extract_id_from_title(&title, &selectors.id_pattern);
+
+    let canonical_url = document
+        .select(&Selector::parse("link[rel=canonical]")?)
         .next()
-        .ok_or("Post body not found using any selector")?;
+        .and_then(|link| link.value().attr("href"))
+        .map(helpers::canonicalize_post_url);
+
+    let (content, blocks, embeds, attachments, links, outgoing, content_html, content_md) =
+        document
+            .select(&post_body_selector)
+            .filter_map(|element| {
+                let text = element.text().collect::<Vec<_>>().join(" ");
+                if !text.is_empty() {
+                    let html = options
+                        .keep_html
+                        .then(|| ammonia::clean(&element.inner_html()));
+                    let md = options.markdown.then(|| convert::html_to_markdown(element));
+                    let blocks = convert::html_to_blocks(element);
+                    let embeds = embeds::extract_embeds(element);
+                    let attachments = attachments::extract_attachments(element, url);
+                    let links = links::extract_links(element, url);
+                    let outgoing = links::extract_post_references(element, url);
+                    Some((text, blocks, embeds, attachments, links, outgoing, html, md))
+                } else {
+                    None
+                }
+            })
+            .next()
+            .ok_or_else(|| ScrapeError::parse(url, "post body not found using any selector"))?;
 
-    let date = document
+    let date_raw = document
         .select(&date_header_selector)
         .next()
-        .map(|n| n.text().collect::<Vec<_>>().join(" "));
+        .map(|n| n.text().collect::<Vec<_>>().join(" "))
+        .filter(|text| !text.trim().is_empty());
+    let date = date_raw
+        .as_deref()
+        .and_then(|raw| dates::parse_date_header(raw, options.date_locale))
+        .or_else(|| dates::published_date(&document));
+
+    let author = document
+        .select(&author_selector)
+        .map(|n| n.text().collect::<Vec<_>>().join(" "))
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|text| !text.is_empty())
+        .or_else(|| meta_content(&document, "meta[name=author]"));
+
+    let comments = extract_comments(&document)?;
+
+    let mut labels = Vec::new();
+    for label in document.select(&labels_selector) {
+        let label = label.text().collect::<String>().trim().to_string();
+        if !label.is_empty() && !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
 
-    let mut images = HashSet::new();
-    if let Some(post_outer) = document.select(&Selector::parse(".post-outer")?).next() {
+    let mut images = BTreeSet::new();
+    if let Some(post_outer) = document
+        .select(&Selector::parse(&selectors.post_outer)?)
+        .next()
+    {
         let img_selector = Selector::parse("img")?;
 
         for img in post_outer.select(&img_selector) {
@@ -311,12 +1069,56 @@ fn fetch_and_process_post(url: &str) -> Result<Post, Box<dyn std::error::Error>>
         }
     }
 
-    Ok(Post {
+    let now = Utc::now().to_rfc3339();
+    let mut post = Post {
+        schema_version: helpers::SCHEMA_VERSION,
         id,
+        id_assigned_manually: false,
         title,
         content,
+        blocks,
+        content_html,
+        content_md,
+        word_count: 0,
+        reading_minutes: 0,
+        excerpt: None,
         URL: url.to_string(),
+        canonical_url,
         date,
+        stored_date: None,
+        date_raw,
+        author,
+        published: dates::published_time(&document),
+        updated: dates::updated_time(&document),
+        description: meta_content(&document, "meta[name=description]"),
+        og_title: meta_content(&document, "meta[property='og:title']"),
+        og_description: meta_content(&document, "meta[property='og:description']"),
+        og_image: meta_content(&document, "meta[property='og:image']"),
         images,
-    })
+        embeds,
+        attachments,
+        links,
+        outgoing,
+        incoming: Vec::new(),
+        labels,
+        comments,
+        content_hash: None,
+        source: None,
+        last_checked: Some(now.clone()),
+        last_modified: Some(now),
+    };
+    post.content_hash = Some(helpers::content_hash(&post));
+    helpers::fill_reading_stats(&mut post, options.excerpt_words);
+
+    Ok(post)
+}
+
+/// Content of the first non-empty `<meta>` tag matching `selector`.
+fn meta_content(document: &Html, selector: &str) -> Option<String> {
+    document
+        .select(&Selector::parse(selector).ok()?)
+        .filter_map(|meta| meta.value().attr("content"))
+        .map(str::trim)
+        .find(|content| !content.is_empty())
+        .map(str::to_string)
 }
@@ -0,0 +1,276 @@
+use crate::convert;
+use crate::error::ScrapeError;
+use crate::Post;
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{ErrorKind, IsTerminal};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, SnippetGenerator, TantivyDocument};
+use tracing::{info, warn};
+
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+const SNIPPET_CHARS: usize = 200;
+
+/// Full-text index over a backup, kept as `<backup>.index/` unless another
+/// directory is given. The index is always rebuilt from scratch, so it never
+/// holds posts that have since been removed from the backup.
+pub fn default_index_dir(backup_path: &Path) -> PathBuf {
+    let mut path = backup_path.as_os_str().to_owned();
+    path.push(".index");
+    PathBuf::from(path)
+}
+
+struct Fields {
+    url: Field,
+    title: Field,
+    date: Field,
+    content: Field,
+    labels: Field,
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        url: builder.add_text_field("url", STRING | STORED),
+        title: builder.add_text_field("title", TEXT | STORED),
+        date: builder.add_text_field("date", STORED),
+        content: builder.add_text_field("content", TEXT | STORED),
+        labels: builder.add_text_field("labels", TEXT | STORED),
+    };
+    (builder.build(), fields)
+}
+
+impl Fields {
+    fn lookup(schema: &Schema) -> Result<Self, ScrapeError> {
+        Ok(Fields {
+            url: schema.get_field("url")?,
+            title: schema.get_field("title")?,
+            date: schema.get_field("date")?,
+            content: schema.get_field("content")?,
+            labels: schema.get_field("labels")?,
+        })
+    }
+}
+
+/// One ranked match, with the byte ranges of `snippet` that matched the query.
+pub struct SearchHit {
+    pub title: String,
+    pub date: Option<String>,
+    pub url: String,
+    pub snippet: String,
+    pub highlighted: Vec<Range<usize>>,
+}
+
+pub fn build_index(posts: &[Post], dir: &Path) -> Result<(), ScrapeError> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    fs::create_dir_all(dir)?;
+
+    let (schema, fields) = schema();
+    let index = Index::create_in_dir(dir, schema)?;
+    let mut writer = index.writer(WRITER_HEAP_BYTES)?;
+
+    for post in posts {
+        let mut document = doc!(
+            fields.url => post.URL.as_str(),
+            fields.title => convert::html_to_text(&post.title),
+            fields.content => post.content.as_str(),
+        );
+        if let Some(date) = post.date {
+            document.add_text(fields.date, date.to_string());
+        }
+        for label in &post.labels {
+            document.add_text(fields.labels, label);
+        }
+        writer.add_document(document)?;
+    }
+    writer.commit()?;
+
+    info!(posts = posts.len(), dir = %dir.display(), "built search index");
+    println!("Indexed {} posts into {}", posts.len(), dir.display());
+    Ok(())
+}
+
+/// Queries the index in `dir`, or scans `posts` when no index has been built.
+pub fn search(
+    query: &str,
+    dir: &Path,
+    posts: impl FnOnce() -> Result<Vec<Post>, ScrapeError>,
+    limit: usize,
+) -> Result<Vec<SearchHit>, ScrapeError> {
+    if dir.is_dir() {
+        search_index(query, dir, limit)
+    } else {
+        warn!(dir = %dir.display(), "no search index found, scanning the backup instead");
+        Ok(scan_posts(query, &posts()?, limit))
+    }
+}
+
+fn search_index(query: &str, dir: &Path, limit: usize) -> Result<Vec<SearchHit>, ScrapeError> {
+    let index = Index::open_in_dir(dir)?;
+    let fields = Fields::lookup(&index.schema())?;
+    let searcher = index.reader()?.searcher();
+
+    let mut parser =
+        QueryParser::for_index(&index, vec![fields.title, fields.content, fields.labels]);
+    parser.set_field_boost(fields.title, 2.0);
+    let query = parser
+        .parse_query(query)
+        .map_err(|e| ScrapeError::Config(format!("invalid search query: {}", e)))?;
+
+    let mut snippets = SnippetGenerator::create(&searcher, &*query, fields.content)?;
+    snippets.set_max_num_chars(SNIPPET_CHARS);
+
+    let mut hits = Vec::new();
+    for (_score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+        let document: TantivyDocument = searcher.doc(address)?;
+        let text = |field| {
+            document
+                .get_first(field)
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+        };
+        let snippet = snippets.snippet_from_doc(&document);
+        hits.push(SearchHit {
+            title: text(fields.title).unwrap_or_default(),
+            date: text(fields.date),
+            url: text(fields.url).unwrap_or_default(),
+            snippet: snippet.fragment().to_string(),
+            highlighted: snippet.highlighted().to_vec(),
+        });
+    }
+
+    Ok(hits)
+}
+
+/// Fallback for backups without an index: case-insensitive term matching,
+/// ranked by how often the terms appear, with title and label matches
+/// weighted above body matches.
+fn scan_posts(query: &str, posts: &[Post], limit: usize) -> Vec<SearchHit> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| term.trim_matches('"').to_lowercase())
+        .filter(|term| !term.is_empty())
+        .collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(usize, &Post, String)> = posts
+        .iter()
+        .filter_map(|post| {
+            let title = convert::html_to_text(&post.title);
+            let content = post.content.to_lowercase();
+            let labels = post.labels.join(" ").to_lowercase();
+            let score: usize = terms
+                .iter()
+                .map(|term| {
+                    3 * title.to_lowercase().matches(term.as_str()).count()
+                        + 2 * labels.matches(term.as_str()).count()
+                        + content.matches(term.as_str()).count()
+                })
+                .sum();
+            (score > 0).then_some((score, post, title))
+        })
+        .collect();
+    scored.sort_by_key(|(score, _, _)| Reverse(*score));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, post, title)| {
+            let (snippet, highlighted) = scan_snippet(&post.content, &terms);
+            SearchHit {
+                title,
+                date: post.date.map(|date| date.to_string()),
+                url: post.URL.clone(),
+                snippet,
+                highlighted,
+            }
+        })
+        .collect()
+}
+
+/// Cuts a window of the content around the first matching term and records
+/// where every term occurs inside it.
+fn scan_snippet(content: &str, terms: &[String]) -> (String, Vec<Range<usize>>) {
+    // Lowercasing can change byte lengths outside ASCII, so only search the
+    // original text when it maps one to one.
+    let lower = content.to_lowercase();
+    let haystack = if lower.len() == content.len() {
+        lower.as_str()
+    } else {
+        content
+    };
+
+    let first = terms
+        .iter()
+        .filter_map(|term| haystack.find(term.as_str()))
+        .min()
+        .unwrap_or(0);
+    let mut start = first.saturating_sub(SNIPPET_CHARS / 4);
+    while !content.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (start + SNIPPET_CHARS).min(content.len());
+    while !content.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let window = &haystack[start..end];
+    let mut highlighted: Vec<Range<usize>> = terms
+        .iter()
+        .flat_map(|term| {
+            window
+                .match_indices(term.as_str())
+                .map(|(i, m)| i..i + m.len())
+        })
+        .collect();
+    highlighted.sort_by_key(|range| range.start);
+    highlighted.dedup_by(|b, a| b.start < a.end);
+
+    (content[start..end].to_string(), highlighted)
+}
+
+pub fn print_hits(hits: &[SearchHit]) {
+    if hits.is_empty() {
+        println!("No posts matched");
+        return;
+    }
+
+    let (open, close) = if std::io::stdout().is_terminal() {
+        ("\x1b[1m", "\x1b[0m")
+    } else {
+        ("**", "**")
+    };
+    for (rank, hit) in hits.iter().enumerate() {
+        match &hit.date {
+            Some(date) => println!("{}. {} ({})", rank + 1, hit.title, date),
+            None => println!("{}. {}", rank + 1, hit.title),
+        }
+        println!("   {}", hit.url);
+
+        let mut snippet = String::new();
+        let mut last = 0;
+        for range in &hit.highlighted {
+            snippet.push_str(&hit.snippet[last..range.start]);
+            snippet.push_str(open);
+            snippet.push_str(&hit.snippet[range.clone()]);
+            snippet.push_str(close);
+            last = range.end;
+        }
+        snippet.push_str(&hit.snippet[last..]);
+        let snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !snippet.is_empty() {
+            println!("   {}", snippet);
+        }
+        println!();
+    }
+}
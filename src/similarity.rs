@@ -0,0 +1,183 @@
+use crate::convert;
+use crate::error::ScrapeError;
+use crate::helpers;
+use crate::Post;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// Words per shingle. Three-word sequences keep common phrases from making
+/// unrelated posts look alike while still surviving small edits.
+const SHINGLE_WORDS: usize = 3;
+
+/// Posts shorter than this have too few shingles for a meaningful fingerprint.
+const MIN_WORDS: usize = 20;
+
+/// One pair of posts whose content is nearly the same.
+#[derive(Serialize, Debug)]
+struct NearDuplicate {
+    /// Share of matching simhash bits, from 0 to 1
+    similarity: f64,
+    /// Share of word sequences the two posts have in common, from 0 to 1
+    overlap: f64,
+    first: PostRef,
+    second: PostRef,
+}
+
+#[derive(Serialize, Debug)]
+struct PostRef {
+    title: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    words: usize,
+}
+
+/// Compares the content of every pair of posts in a backup and prints the
+/// pairs at least `threshold` similar, most similar first. Posts are
+/// fingerprinted with a 64-bit simhash of their word shingles, so re-publishes
+/// and lightly edited copies are found regardless of id or URL.
+pub fn find_near_duplicates(
+    input: &Path,
+    threshold: f64,
+    output: Option<&Path>,
+) -> Result<(), ScrapeError> {
+    let posts = helpers::read_posts_from_file(input)?;
+    let fingerprints: Vec<(&Post, HashSet<String>, u64)> = posts
+        .iter()
+        .filter(|post| post.word_count >= MIN_WORDS)
+        .map(|post| {
+            let shingles = shingles(&post.content);
+            let hash = simhash(&shingles);
+            (post, shingles, hash)
+        })
+        .collect();
+    println!(
+        "Comparing {} of {} posts in {} (posts under {} words are skipped)",
+        fingerprints.len(),
+        posts.len(),
+        input.display(),
+        MIN_WORDS
+    );
+
+    let mut pairs = Vec::new();
+    for (i, (first, first_shingles, first_hash)) in fingerprints.iter().enumerate() {
+        for (second, second_shingles, second_hash) in &fingerprints[i + 1..] {
+            let similarity = 1.0 - (first_hash ^ second_hash).count_ones() as f64 / 64.0;
+            if similarity < threshold {
+                continue;
+            }
+            let shared = first_shingles.intersection(second_shingles).count();
+            let overlap = shared as f64 / first_shingles.union(second_shingles).count() as f64;
+            pairs.push(NearDuplicate {
+                similarity,
+                overlap,
+                first: post_ref(first),
+                second: post_ref(second),
+            });
+        }
+    }
+    pairs.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(b.overlap.total_cmp(&a.overlap))
+            .then_with(|| a.first.url.cmp(&b.first.url))
+    });
+
+    for pair in &pairs {
+        println!(
+            "{:.2} similar, {:.0}% of word sequences shared",
+            pair.similarity,
+            pair.overlap * 100.0
+        );
+        for post in [&pair.first, &pair.second] {
+            println!(
+                "    {} ({}, {} words) {}",
+                post.title,
+                post.date.as_deref().unwrap_or("undated"),
+                post.words,
+                post.url
+            );
+        }
+    }
+    println!(
+        "{} pairs of posts are at least {:.2} similar",
+        pairs.len(),
+        threshold
+    );
+
+    if let Some(output) = output {
+        fs::write(output, serde_json::to_string_pretty(&pairs)?)?;
+        info!(path = %output.display(), pairs = pairs.len(), "wrote near-duplicate report");
+    }
+
+    Ok(())
+}
+
+/// Parses a --threshold argument, a similarity between 0 and 1.
+pub fn parse_threshold(raw: &str) -> Result<f64, String> {
+    let threshold: f64 = raw.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=1.0).contains(&threshold) {
+        Ok(threshold)
+    } else {
+        Err("must be between 0 and 1".to_string())
+    }
+}
+
+fn post_ref(post: &Post) -> PostRef {
+    PostRef {
+        title: convert::html_to_text(&post.title),
+        url: post.URL.clone(),
+        date: post.date.map(|date| date.to_string()),
+        words: post.word_count,
+    }
+}
+
+/// Lowercased word sequences of SHINGLE_WORDS words, ignoring punctuation.
+fn shingles(text: &str) -> HashSet<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    words
+        .windows(SHINGLE_WORDS)
+        .map(|window| window.join(" "))
+        .collect()
+}
+
+/// Charikar's simhash: each bit is set when more shingle hashes have it set
+/// than not, so similar shingle sets differ in few bits.
+fn simhash(shingles: &HashSet<String>) -> u64 {
+    let mut weights = [0i64; 64];
+    for shingle in shingles {
+        let hash = fnv1a(shingle.as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is stable
+/// across builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
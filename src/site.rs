@@ -0,0 +1,462 @@
+use super::convert;
+use super::export;
+use super::fetcher::Fetcher;
+use super::helpers;
+use crate::error::ScrapeError;
+use crate::Post;
+use chrono::{Datelike, NaiveDate};
+use reqwest::Url;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const RECENT_POSTS_ON_INDEX: usize = 20;
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "png", "gif", "webp", "svg"];
+
+const STYLESHEET: &str = "body { max-width: 48rem; margin: 0 auto; padding: 1rem; font-family: Georgia, serif; line-height: 1.6; }
+nav a { margin-right: 1rem; }
+img { max-width: 100%; height: auto; }
+.date, .labels, .source { color: #666; font-size: 0.9rem; }
+.comments { border-top: 1px solid #ccc; margin-top: 2rem; }
+#results li { margin-bottom: 1rem; }
+.excerpt { margin: 0.25rem 0 0.75rem; }
+";
+
+const SEARCH_SCRIPT: &str = r#"(function () {
+  var input = document.getElementById("q");
+  var results = document.getElementById("results");
+  function render() {
+    var terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    results.innerHTML = "";
+    if (!terms.length) return;
+    var hits = window.SEARCH_INDEX.filter(function (post) {
+      var text = (post.title + " " + post.labels.join(" ") + " " + post.text).toLowerCase();
+      return terms.every(function (term) { return text.indexOf(term) !== -1; });
+    }).sort(function (a, b) {
+      function score(post) {
+        var title = post.title.toLowerCase();
+        return terms.filter(function (term) { return title.indexOf(term) !== -1; }).length;
+      }
+      return score(b) - score(a);
+    });
+    hits.slice(0, 100).forEach(function (post) {
+      var item = document.createElement("li");
+      var link = document.createElement("a");
+      link.href = post.path;
+      link.textContent = post.title;
+      item.appendChild(link);
+      if (post.date) {
+        var date = document.createElement("span");
+        date.className = "date";
+        date.textContent = " " + post.date;
+        item.appendChild(date);
+      }
+      var at = post.text.toLowerCase().indexOf(terms[0]);
+      var snippet = document.createElement("div");
+      snippet.textContent = post.text.substr(Math.max(0, at - 60), 200);
+      item.appendChild(snippet);
+      results.appendChild(item);
+    });
+  }
+  input.addEventListener("input", render);
+})();
+"#;
+
+/// Entry in the client-side search index, written as a script so search also
+/// works when the site is opened straight from disk.
+#[derive(Serialize)]
+struct SearchEntry<'a> {
+    title: &'a str,
+    path: &'a str,
+    date: Option<NaiveDate>,
+    labels: &'a [String],
+    text: &'a str,
+}
+
+struct SitePost<'a> {
+    post: &'a Post,
+    title: String,
+    path: String,
+    month: Option<(i32, u32)>,
+}
+
+/// Writes a browsable static copy of the archive to `dir`: one page per post
+/// with local images, index pages by year, month and label, and a search page.
+pub fn export_site(fetcher: &Fetcher, input: &Path, dir: &Path) -> Result<(), ScrapeError> {
+    let mut posts = helpers::read_posts_from_file(input)?;
+    helpers::sort_backup(&mut posts)?;
+    println!(
+        "Building static site from {} posts found in {}",
+        posts.len(),
+        input.display()
+    );
+
+    fs::create_dir_all(dir.join("images"))?;
+    let site_posts: Vec<SitePost> = posts
+        .iter()
+        .map(|post| SitePost {
+            post,
+            title: convert::html_to_text(&post.title),
+            path: post_path(post),
+            month: helpers::post_date(post).map(|date| (date.year(), date.month())),
+        })
+        .collect();
+
+    let label_stems = label_stems(&posts);
+    let mut downloaded: HashMap<String, Option<String>> = HashMap::new();
+    for site_post in &site_posts {
+        let mut local_images = Vec::new();
+        for image_url in &site_post.post.images {
+            let local = downloaded
+                .entry(image_url.clone())
+                .or_insert_with(|| download_image(fetcher, dir, image_url));
+            if let Some(local) = local {
+                let root = root_prefix(&site_post.path);
+                local_images.push((image_url.clone(), format!("{}{}", root, local)));
+            }
+        }
+        write_page(
+            dir,
+            &site_post.path,
+            &render_post(site_post, &local_images, &label_stems),
+        )?;
+    }
+
+    write_indexes(dir, &site_posts, &label_stems, &helpers::blog_name(&posts))?;
+    write_search(dir, &site_posts)?;
+    fs::write(dir.join("style.css"), STYLESHEET)?;
+
+    println!(
+        "Static site with {} posts and {} images written to {}",
+        site_posts.len(),
+        downloaded.values().filter(|local| local.is_some()).count(),
+        dir.display()
+    );
+    Ok(())
+}
+
+/// File name stem of every label page. Labels that only differ in case or
+/// punctuation would share a stem, so later ones in sorted order are numbered,
+/// as is a label that would overwrite `labels/index.html`.
+fn label_stems(posts: &[Post]) -> HashMap<&str, String> {
+    let labels: BTreeSet<&str> = posts
+        .iter()
+        .flat_map(|post| post.labels.iter().map(String::as_str))
+        .collect();
+    let mut taken: HashSet<String> = HashSet::from(["index".to_string()]);
+    labels
+        .into_iter()
+        .map(|label| {
+            let stem = crate::label_file_stem(label);
+            let mut name = stem.clone();
+            let mut suffix = 2;
+            while !taken.insert(name.clone()) {
+                name = format!("{}-{}", stem, suffix);
+                suffix += 1;
+            }
+            (label, name)
+        })
+        .collect()
+}
+
+/// Keeps Blogger's `/YYYY/MM/slug.html` layout under `posts/` so links stay
+/// recognizable, falling back to a hash of the URL for anything else.
+fn post_path(post: &Post) -> String {
+    let path = Url::parse(&post.URL)
+        .ok()
+        .map(|url| url.path().trim_start_matches('/').to_string())
+        .filter(|path| {
+            path.ends_with(".html")
+                && path
+                    .split('/')
+                    .all(|segment| !segment.is_empty() && segment != "..")
+        });
+    match path {
+        Some(path) => format!("posts/{}", path),
+        None => format!(
+            "posts/{}.html",
+            &helpers::sha256_hex(post.URL.as_bytes())[..16]
+        ),
+    }
+}
+
+fn root_prefix(path: &str) -> String {
+    "../".repeat(path.matches('/').count())
+}
+
+/// Stores an image as `images/<hash>.<ext>`, reusing a copy left by an
+/// earlier export. Returns the path relative to the site root.
+fn download_image(fetcher: &Fetcher, dir: &Path, image_url: &str) -> Option<String> {
+    let stem = format!(
+        "images/{}",
+        &helpers::sha256_hex(image_url.as_bytes())[..16]
+    );
+    for extension in IMAGE_EXTENSIONS {
+        let local = format!("{}.{}", stem, extension);
+        if dir.join(&local).exists() {
+            return Some(local);
+        }
+    }
+
+    match fetcher.fetch_bytes(image_url) {
+        Ok((bytes, content_type)) => {
+            let mime = content_type
+                .filter(|ct| ct.starts_with("image/"))
+                .unwrap_or_else(|| export::guess_image_mime(image_url));
+            let local = format!("{}.{}", stem, export::image_extension(&mime));
+            match fs::write(dir.join(&local), bytes) {
+                Ok(()) => Some(local),
+                Err(e) => {
+                    eprintln!("Failed to save image {}: {}", image_url, e);
+                    None
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to download image {}: {}", image_url, e);
+            None
+        }
+    }
+}
+
+fn write_page(dir: &Path, path: &str, contents: &str) -> Result<(), ScrapeError> {
+    let path = dir.join(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n\
+         <html lang=\"en\">\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n\
+         <link rel=\"stylesheet\" href=\"{root}style.css\">\n\
+         </head>\n\
+         <body>\n\
+         <nav><a href=\"{root}index.html\">Home</a><a href=\"{root}labels/index.html\">Labels</a><a href=\"{root}search.html\">Search</a></nav>\n\
+         <main>\n{body}</main>\n\
+         </body>\n\
+         </html>\n",
+        title = convert::escape_xml(title),
+        root = root,
+        body = body
+    )
+}
+
+fn render_post(
+    site_post: &SitePost,
+    local_images: &[(String, String)],
+    label_stems: &HashMap<&str, String>,
+) -> String {
+    let post = site_post.post;
+    let root = root_prefix(&site_post.path);
+    let mut body = format!("<h1>{}</h1>\n", convert::escape_xml(&site_post.title));
+    let mut byline: Vec<String> = post.date.iter().map(|date| date.to_string()).collect();
+    if post.reading_minutes > 0 {
+        byline.push(format!("{} min read", post.reading_minutes));
+    }
+    if !byline.is_empty() {
+        body.push_str(&format!("<p class=\"date\">{}</p>\n", byline.join(" · ")));
+    }
+    if !post.labels.is_empty() {
+        let links: Vec<String> = post
+            .labels
+            .iter()
+            .map(|label| {
+                format!(
+                    "<a href=\"{}labels/{}.html\">{}</a>",
+                    root,
+                    label_stems[label.as_str()],
+                    convert::escape_xml(label)
+                )
+            })
+            .collect();
+        body.push_str(&format!("<p class=\"labels\">{}</p>\n", links.join(", ")));
+    }
+
+    let html = match &post.content_html {
+        Some(html) => format!("{}\n", html),
+        None => convert::blocks_to_html(&helpers::post_blocks(post)),
+    };
+    let (html, unplaced) = export::localize_images(&html, local_images);
+    body.push_str(&html);
+    for local in unplaced {
+        body.push_str(&format!("<p><img src=\"{}\" alt=\"\"></p>\n", local));
+    }
+
+    if !post.comments.is_empty() {
+        body.push_str("<section class=\"comments\">\n<h2>Comments</h2>\n");
+        for comment in &post.comments {
+            body.push_str(&format!(
+                "<p><strong>{}</strong> <span class=\"date\">{}</span><br>{}</p>\n",
+                convert::escape_xml(&comment.author),
+                convert::escape_xml(comment.date.as_deref().unwrap_or("")),
+                convert::escape_xml(&comment.body)
+            ));
+        }
+        body.push_str("</section>\n");
+    }
+
+    body.push_str(&format!(
+        "<p class=\"source\">Originally published at <a href=\"{0}\">{0}</a></p>\n",
+        convert::escape_xml(&post.URL)
+    ));
+    page(&site_post.title, &root, &body)
+}
+
+fn post_list(posts: &[&SitePost], root: &str) -> String {
+    let mut list = String::from("<ul>\n");
+    for site_post in posts {
+        list.push_str(&format!(
+            "<li><a href=\"{}{}\">{}</a>",
+            root,
+            site_post.path,
+            convert::escape_xml(&site_post.title)
+        ));
+        if let Some(date) = &site_post.post.date {
+            list.push_str(&format!(" <span class=\"date\">{}</span>", date));
+        }
+        if let Some(excerpt) = &site_post.post.excerpt {
+            list.push_str(&format!(
+                "\n<p class=\"excerpt\">{}</p>",
+                convert::escape_xml(excerpt)
+            ));
+        }
+        list.push_str("</li>\n");
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+fn month_name(year: i32, month: u32) -> String {
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .map(|date| date.format("%B %Y").to_string())
+        .unwrap_or_else(|| format!("{}-{:02}", year, month))
+}
+
+fn write_indexes(
+    dir: &Path,
+    posts: &[SitePost],
+    label_stems: &HashMap<&str, String>,
+    blog_name: &str,
+) -> Result<(), ScrapeError> {
+    let mut by_month: BTreeMap<i32, BTreeMap<u32, Vec<&SitePost>>> = BTreeMap::new();
+    let mut by_label: BTreeMap<&str, Vec<&SitePost>> = BTreeMap::new();
+    for site_post in posts {
+        if let Some((year, month)) = site_post.month {
+            by_month
+                .entry(year)
+                .or_default()
+                .entry(month)
+                .or_default()
+                .push(site_post);
+        }
+        for label in &site_post.post.labels {
+            by_label.entry(label).or_default().push(site_post);
+        }
+    }
+
+    let mut archive = String::from("<h2>Archive</h2>\n<ul>\n");
+    for (year, months) in by_month.iter().rev() {
+        let count: usize = months.values().map(Vec::len).sum();
+        archive.push_str(&format!(
+            "<li><a href=\"archive/{0}/index.html\">{0}</a> ({1})</li>\n",
+            year, count
+        ));
+
+        let mut year_body = format!("<h1>{}</h1>\n", year);
+        for (month, month_posts) in months.iter().rev() {
+            year_body.push_str(&format!(
+                "<h2><a href=\"{:02}.html\">{}</a></h2>\n",
+                month,
+                month_name(*year, *month)
+            ));
+            year_body.push_str(&post_list(month_posts, "../../"));
+
+            let title = month_name(*year, *month);
+            let month_body = format!("<h1>{}</h1>\n{}", title, post_list(month_posts, "../../"));
+            write_page(
+                dir,
+                &format!("archive/{}/{:02}.html", year, month),
+                &page(&title, "../../", &month_body),
+            )?;
+        }
+        write_page(
+            dir,
+            &format!("archive/{}/index.html", year),
+            &page(&year.to_string(), "../../", &year_body),
+        )?;
+    }
+    archive.push_str("</ul>\n");
+
+    let mut labels_body = String::from("<h1>Labels</h1>\n<ul>\n");
+    for (label, label_posts) in &by_label {
+        let stem = &label_stems[label];
+        labels_body.push_str(&format!(
+            "<li><a href=\"{}.html\">{}</a> ({})</li>\n",
+            stem,
+            convert::escape_xml(label),
+            label_posts.len()
+        ));
+        let body = format!(
+            "<h1>{}</h1>\n{}",
+            convert::escape_xml(label),
+            post_list(label_posts, "../")
+        );
+        write_page(
+            dir,
+            &format!("labels/{}.html", stem),
+            &page(label, "../", &body),
+        )?;
+    }
+    labels_body.push_str("</ul>\n");
+    write_page(
+        dir,
+        "labels/index.html",
+        &page("Labels", "../", &labels_body),
+    )?;
+
+    let recent: Vec<&SitePost> = posts.iter().take(RECENT_POSTS_ON_INDEX).collect();
+    let index_body = format!(
+        "<h1>{}</h1>\n<h2>Recent posts</h2>\n{}{}",
+        convert::escape_xml(blog_name),
+        post_list(&recent, ""),
+        archive
+    );
+    write_page(dir, "index.html", &page(blog_name, "", &index_body))
+}
+
+fn write_search(dir: &Path, posts: &[SitePost]) -> Result<(), ScrapeError> {
+    let entries: Vec<SearchEntry> = posts
+        .iter()
+        .map(|site_post| SearchEntry {
+            title: &site_post.title,
+            path: &site_post.path,
+            date: site_post.post.date,
+            labels: &site_post.post.labels,
+            text: &site_post.post.content,
+        })
+        .collect();
+    fs::write(
+        dir.join("search-index.js"),
+        format!(
+            "window.SEARCH_INDEX = {};\n",
+            serde_json::to_string(&entries)?
+        ),
+    )?;
+    fs::write(dir.join("search.js"), SEARCH_SCRIPT)?;
+
+    let body = "<h1>Search</h1>\n\
+                <input id=\"q\" type=\"search\" placeholder=\"Search posts\" autofocus>\n\
+                <ul id=\"results\"></ul>\n\
+                <script src=\"search-index.js\"></script>\n\
+                <script src=\"search.js\"></script>\n";
+    write_page(dir, "search.html", &page("Search", "", body))
+}
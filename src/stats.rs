@@ -0,0 +1,179 @@
+use crate::convert;
+use crate::error::ScrapeError;
+use crate::helpers;
+use crate::Post;
+use chrono::Datelike;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Number of labels listed in the text summary; --json lists them all.
+const TOP_LABELS: usize = 10;
+
+/// Number of posts listed as the largest and the smallest.
+const EXTREMES: usize = 5;
+
+/// Summary figures for a whole archive, printed by the stats subcommand.
+#[derive(Serialize, Debug)]
+struct ArchiveStats {
+    posts: usize,
+    without_id: usize,
+    without_date: usize,
+    comments: usize,
+    images: usize,
+    posts_with_images: usize,
+    total_words: usize,
+    average_words: f64,
+    /// Posts per year, keyed "YYYY", with undated posts under "undated"
+    per_year: BTreeMap<String, usize>,
+    /// Posts per month, keyed "YYYY-MM"
+    per_month: BTreeMap<String, usize>,
+    /// Label frequencies, most used first
+    labels: Vec<LabelCount>,
+    largest: Vec<PostSize>,
+    smallest: Vec<PostSize>,
+}
+
+#[derive(Serialize, Debug)]
+struct LabelCount {
+    label: String,
+    posts: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct PostSize {
+    title: String,
+    url: String,
+    words: usize,
+}
+
+/// Prints statistics about a backup file, as text or as a JSON object.
+pub fn print_stats(input: &Path, json: bool) -> Result<(), ScrapeError> {
+    let backup = helpers::read_posts_from_file(input)?;
+    let stats = archive_stats(&backup);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("{} contains {} posts", input.display(), stats.posts);
+    println!(
+        "{} posts have no id and {} have no date",
+        stats.without_id, stats.without_date
+    );
+    println!(
+        "{} images in {} posts and {} comments were archived",
+        stats.images, stats.posts_with_images, stats.comments
+    );
+    println!(
+        "{} words in total, {:.0} per post on average",
+        stats.total_words, stats.average_words
+    );
+
+    if !stats.per_year.is_empty() {
+        println!("Posts per year:");
+        for (year, count) in &stats.per_year {
+            let months: Vec<String> = stats
+                .per_month
+                .iter()
+                .filter_map(|(month, count)| {
+                    let month = month.strip_prefix(year.as_str())?.strip_prefix('-')?;
+                    Some(format!("{}: {}", month, count))
+                })
+                .collect();
+            if months.is_empty() {
+                println!("  {:<8} {:>5}", year, count);
+            } else {
+                println!("  {:<8} {:>5}  ({})", year, count, months.join(", "));
+            }
+        }
+    }
+
+    if stats.labels.is_empty() {
+        println!("No labels found");
+    } else {
+        println!("{} distinct labels, most used first:", stats.labels.len());
+        for label in stats.labels.iter().take(TOP_LABELS) {
+            println!("  {:>5}  {}", label.posts, label.label);
+        }
+    }
+
+    for (heading, posts) in [("Largest", &stats.largest), ("Smallest", &stats.smallest)] {
+        if posts.is_empty() {
+            continue;
+        }
+        println!("{} posts by word count:", heading);
+        for post in posts {
+            println!("  {:>7}  {} ({})", post.words, post.title, post.url);
+        }
+    }
+
+    Ok(())
+}
+
+fn archive_stats(backup: &[Post]) -> ArchiveStats {
+    let mut per_year: BTreeMap<String, usize> = BTreeMap::new();
+    let mut per_month: BTreeMap<String, usize> = BTreeMap::new();
+    let mut label_counts: HashMap<&str, usize> = HashMap::new();
+    for post in backup {
+        match helpers::post_date(post) {
+            Some(date) => {
+                *per_year.entry(date.year().to_string()).or_default() += 1;
+                *per_month
+                    .entry(format!("{}-{:02}", date.year(), date.month()))
+                    .or_default() += 1;
+            }
+            None => *per_year.entry("undated".to_string()).or_default() += 1,
+        }
+        for label in &post.labels {
+            *label_counts.entry(label.as_str()).or_default() += 1;
+        }
+    }
+
+    let mut labels: Vec<LabelCount> = label_counts
+        .into_iter()
+        .map(|(label, posts)| LabelCount {
+            label: label.to_string(),
+            posts,
+        })
+        .collect();
+    labels.sort_by(|a, b| b.posts.cmp(&a.posts).then(a.label.cmp(&b.label)));
+
+    let mut by_size: Vec<&Post> = backup.iter().collect();
+    by_size.sort_by(|a, b| b.word_count.cmp(&a.word_count).then(a.URL.cmp(&b.URL)));
+    let post_size = |post: &&Post| PostSize {
+        title: convert::html_to_text(&post.title),
+        url: post.URL.clone(),
+        words: post.word_count,
+    };
+    let largest: Vec<PostSize> = by_size.iter().take(EXTREMES).map(post_size).collect();
+    // Small archives would otherwise list the same posts twice
+    let smallest: Vec<PostSize> = by_size[largest.len()..]
+        .iter()
+        .rev()
+        .take(EXTREMES)
+        .map(post_size)
+        .collect();
+
+    let total_words: usize = backup.iter().map(|post| post.word_count).sum();
+    ArchiveStats {
+        posts: backup.len(),
+        without_id: backup.iter().filter(|post| post.id.is_none()).count(),
+        without_date: backup.iter().filter(|post| post.date.is_none()).count(),
+        comments: backup.iter().map(|post| post.comments.len()).sum(),
+        images: backup.iter().map(|post| post.images.len()).sum(),
+        posts_with_images: backup.iter().filter(|post| !post.images.is_empty()).count(),
+        total_words,
+        average_words: if backup.is_empty() {
+            0.0
+        } else {
+            total_words as f64 / backup.len() as f64
+        },
+        per_year,
+        per_month,
+        labels,
+        largest,
+        smallest,
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::ScrapeError;
+use chrono::{SecondsFormat, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Url};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Appends request/response pairs to a WARC 1.0 file that replay tools such
+/// as pywb can read. Records are gzipped one per member when the path ends
+/// in `.gz`, as is conventional for `.warc.gz` files.
+pub struct WarcWriter {
+    file: Mutex<File>,
+    gzip: bool,
+}
+
+impl WarcWriter {
+    pub fn create(path: &Path) -> Result<Self, ScrapeError> {
+        let writer = WarcWriter {
+            file: Mutex::new(File::create(path)?),
+            gzip: path.extension().is_some_and(|extension| extension == "gz"),
+        };
+        let info = format!(
+            "software: scrape_blogger/{}\r\nformat: WARC File Format 1.0\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        writer.write_record(
+            "warcinfo",
+            None,
+            None,
+            "application/warc-fields",
+            info.as_bytes(),
+        )?;
+        Ok(writer)
+    }
+
+    pub fn record_exchange(
+        &self,
+        url: &str,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        response_headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<(), ScrapeError> {
+        let mut response = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        )
+        .into_bytes();
+        write_headers(&mut response, response_headers);
+        response.extend_from_slice(body);
+        let response_id = self.write_record(
+            "response",
+            Some(url),
+            None,
+            "application/http; msgtype=response",
+            &response,
+        )?;
+
+        let parsed = Url::parse(url).map_err(|e| ScrapeError::parse(url, &e.to_string()))?;
+        let mut target = parsed.path().to_string();
+        if let Some(query) = parsed.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let mut host = parsed.host_str().unwrap_or("").to_string();
+        if let Some(port) = parsed.port() {
+            host.push_str(&format!(":{}", port));
+        }
+        let mut request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n", target, host).into_bytes();
+        write_headers(&mut request, request_headers);
+        self.write_record(
+            "request",
+            Some(url),
+            Some(&response_id),
+            "application/http; msgtype=request",
+            &request,
+        )?;
+
+        Ok(())
+    }
+
+    /// Writes a single record and returns its WARC-Record-ID.
+    fn write_record(
+        &self,
+        warc_type: &str,
+        target_uri: Option<&str>,
+        concurrent_to: Option<&str>,
+        content_type: &str,
+        block: &[u8],
+    ) -> Result<String, ScrapeError> {
+        let record_id = format!("<urn:uuid:{}>", Uuid::new_v4());
+        let mut record = format!(
+            "WARC/1.0\r\nWARC-Type: {}\r\nWARC-Record-ID: {}\r\nWARC-Date: {}\r\n",
+            warc_type,
+            record_id,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        if let Some(uri) = target_uri {
+            record.push_str(&format!("WARC-Target-URI: {}\r\n", uri));
+        }
+        if let Some(id) = concurrent_to {
+            record.push_str(&format!("WARC-Concurrent-To: {}\r\n", id));
+        }
+        record.push_str(&format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            content_type,
+            block.len()
+        ));
+
+        let mut bytes = record.into_bytes();
+        bytes.extend_from_slice(block);
+        bytes.extend_from_slice(b"\r\n\r\n");
+
+        let mut file = self.file.lock()?;
+        if self.gzip {
+            let mut encoder = GzEncoder::new(&mut *file, Compression::default());
+            encoder.write_all(&bytes)?;
+            encoder.finish()?;
+        } else {
+            file.write_all(&bytes)?;
+        }
+
+        Ok(record_id)
+    }
+}
+
+fn write_headers(out: &mut Vec<u8>, headers: &HeaderMap) {
+    for (name, value) in headers {
+        out.extend_from_slice(name.as_str().as_bytes());
+        out.extend_from_slice(b": ");
+        out.extend_from_slice(value.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b"\r\n");
+}